@@ -0,0 +1,163 @@
+// Runs an Intcode program from a file:
+//
+//   intcode [--trace] [--budget <n>] [--ascii] <program> [input...]
+//
+// Inputs given after the program are all the program gets. Without any it
+// reads them from stdin as it needs them, numbers separated by commas or
+// whitespace. Each output is printed on its own line, followed by the final
+// value of address 0.
+//
+//   --trace         print each instruction to stderr as it runs
+//   --budget <n>    stop with an error after n instructions
+//   --ascii         treat input and output as text, each input is a line
+use std::error::Error;
+use std::io::{self, BufRead};
+use std::process;
+
+use advent::intcode::loader::load_file;
+use advent::intcode::trace::TextTracer;
+use advent::intcode::{IntcodeError, Machine, NoTrace, Status, Tracer};
+
+const USAGE: &str = "usage: intcode [--trace] [--budget <n>] [--ascii] <program> [input...]";
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    trace: bool,
+    budget: Option<u64>,
+    ascii: bool,
+    program: String,
+    inputs: Vec<String>,
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut program = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--trace" => options.trace = true,
+            "--ascii" => options.ascii = true,
+            "--budget" => {
+                let n = args.next().ok_or("--budget needs a number")?;
+                let n = n.parse().map_err(|_| format!("bad budget {:?}", n))?;
+                options.budget = Some(n);
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown flag {}", flag)),
+            _ if program.is_none() => program = Some(arg),
+            _ => options.inputs.push(arg),
+        }
+    }
+
+    options.program = program.ok_or("no program given")?;
+    Ok(options)
+}
+
+fn parse_value(text: &str) -> Result<i64, String> {
+    text.parse().map_err(|_| format!("bad input {:?}", text))
+}
+
+// Runs to the end, printing outputs as they come and reading input from
+// stdin when the queue runs dry, if it may.
+fn run_numbers<T: Tracer>(machine: &mut Machine<T>, stdin: bool) -> Result<(), Box<dyn Error>> {
+    let stdin = if stdin { Some(io::stdin()) } else { None };
+
+    loop {
+        match machine.resume()? {
+            Status::Output(value) => println!("{}", value),
+            Status::NeedsInput => {
+                let mut line = String::new();
+                let read = match &stdin {
+                    Some(stdin) => stdin.lock().read_line(&mut line)?,
+                    None => 0,
+                };
+                if read == 0 {
+                    return Err(IntcodeError::InputExhausted { pc: machine.pc() }.into());
+                }
+
+                let values = line
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|token| !token.is_empty())
+                    .map(parse_value)
+                    .collect::<Result<Vec<i64>, String>>()?;
+                machine.provide_inputs(values);
+            }
+            Status::Halted => return Ok(()),
+        }
+    }
+}
+
+fn run(options: Options) -> Result<(), Box<dyn Error>> {
+    let program = load_file(&options.program)?;
+
+    let tracer: Box<dyn Tracer> = if options.trace {
+        Box::new(TextTracer::new(io::stderr()))
+    } else {
+        Box::new(NoTrace)
+    };
+    let mut machine = Machine::new(&program).with_tracer(tracer);
+    machine.set_budget(options.budget);
+
+    if options.ascii {
+        for line in &options.inputs {
+            machine.provide_line(line)?;
+        }
+        let stdin = io::stdin();
+        machine.play(stdin.lock(), io::stdout())?;
+    } else {
+        for input in &options.inputs {
+            machine.provide_input(parse_value(input)?);
+        }
+        run_numbers(&mut machine, options.inputs.is_empty())?;
+    }
+
+    println!("[0] = {}", machine.read(0));
+    Ok(())
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("intcode: {}\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(err) = run(options) {
+        eprintln!("intcode: {}", err);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn args() {
+        assert_eq!(
+            parse(&["--budget", "100", "prog.txt", "5", "-3", "--trace"]),
+            Ok(Options {
+                trace: true,
+                budget: Some(100),
+                ascii: false,
+                program: "prog.txt".to_string(),
+                inputs: vec!["5".to_string(), "-3".to_string()],
+            })
+        );
+
+        assert_eq!(parse(&[]), Err("no program given".to_string()));
+        assert_eq!(
+            parse(&["prog.txt", "--budget"]),
+            Err("--budget needs a number".to_string())
+        );
+        assert_eq!(
+            parse(&["--quiet", "prog.txt"]),
+            Err("unknown flag --quiet".to_string())
+        );
+    }
+}
@@ -0,0 +1,800 @@
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryInto;
+use std::fmt;
+
+pub mod amplifiers;
+pub mod ascii;
+pub mod asm;
+pub mod cfg;
+pub mod compile;
+pub mod debugger;
+mod decode;
+pub mod disasm;
+pub mod extension;
+#[cfg(test)]
+mod fixtures;
+pub mod fuzz;
+mod history;
+mod limits;
+pub mod loader;
+mod memory;
+pub mod network;
+pub mod snapshot;
+pub mod sweep;
+pub mod symbolic;
+pub mod trace;
+
+pub use decode::DecodeCache;
+use decode::{Decoded, Decoding};
+pub use extension::{Effect, Extension};
+use history::Undo;
+use limits::SeenStates;
+pub use memory::Memory;
+pub use trace::{ExtensionStep, NoTrace, Step, Tracer};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OpCode {
+    Add,
+    Mult,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustRelativeBase,
+    Halt,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArgMode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl OpCode {
+    pub const ALL: [OpCode; 10] = [
+        OpCode::Add,
+        OpCode::Mult,
+        OpCode::Input,
+        OpCode::Output,
+        OpCode::JumpIfTrue,
+        OpCode::JumpIfFalse,
+        OpCode::LessThan,
+        OpCode::Equals,
+        OpCode::AdjustRelativeBase,
+        OpCode::Halt,
+    ];
+
+    // The low two digits of an instruction, the inverse of parse_code.
+    pub fn code(self) -> i64 {
+        match self {
+            OpCode::Add => 1,
+            OpCode::Mult => 2,
+            OpCode::Input => 3,
+            OpCode::Output => 4,
+            OpCode::JumpIfTrue => 5,
+            OpCode::JumpIfFalse => 6,
+            OpCode::LessThan => 7,
+            OpCode::Equals => 8,
+            OpCode::AdjustRelativeBase => 9,
+            OpCode::Halt => 99,
+        }
+    }
+
+    // How many parameters follow the instruction.
+    pub fn arity(self) -> usize {
+        match self {
+            OpCode::Add | OpCode::Mult | OpCode::LessThan | OpCode::Equals => 3,
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => 2,
+            OpCode::Input | OpCode::Output | OpCode::AdjustRelativeBase => 1,
+            OpCode::Halt => 0,
+        }
+    }
+
+    // The parameter the instruction writes its result to, if any.
+    pub fn write_param(self) -> Option<usize> {
+        match self {
+            OpCode::Add | OpCode::Mult | OpCode::LessThan | OpCode::Equals => Some(2),
+            OpCode::Input => Some(0),
+            _ => None,
+        }
+    }
+}
+
+impl ArgMode {
+    pub fn code(self) -> i64 {
+        match self {
+            ArgMode::Position => 0,
+            ArgMode::Immediate => 1,
+            ArgMode::Relative => 2,
+        }
+    }
+}
+
+fn parse_mode(pc: usize, mode_code: i64) -> Result<ArgMode, IntcodeError> {
+    if mode_code == 0 {
+        Ok(ArgMode::Position)
+    } else if mode_code == 1 {
+        Ok(ArgMode::Immediate)
+    } else if mode_code == 2 {
+        Ok(ArgMode::Relative)
+    } else {
+        Err(IntcodeError::InvalidMode {
+            pc,
+            mode: mode_code,
+        })
+    }
+}
+
+// code, mode1, mode2, mode3
+// pc is only used to say where a bad code was found.
+pub fn parse_code(
+    pc: usize,
+    code: i64,
+) -> Result<(OpCode, ArgMode, ArgMode, ArgMode), IntcodeError> {
+    let mut parsed_code = code;
+
+    let ten_thousands = parsed_code / 10_000;
+    parsed_code -= 10_000 * ten_thousands;
+
+    let thousands = parsed_code / 1_000;
+    parsed_code -= 1_000 * thousands;
+
+    let hundreads = parsed_code / 100;
+    parsed_code -= 100 * hundreads;
+
+    let op = match parsed_code {
+        1 => OpCode::Add,
+        2 => OpCode::Mult,
+        3 => OpCode::Input,
+        4 => OpCode::Output,
+        5 => OpCode::JumpIfTrue,
+        6 => OpCode::JumpIfFalse,
+        7 => OpCode::LessThan,
+        8 => OpCode::Equals,
+        9 => OpCode::AdjustRelativeBase,
+
+        99 => OpCode::Halt,
+
+        _ => return Err(IntcodeError::UnknownOpcode { pc, code }),
+    };
+
+    let m1 = parse_mode(pc, hundreads)?;
+    let m2 = parse_mode(pc, thousands)?;
+    let m3 = parse_mode(pc, ten_thousands)?;
+
+    Ok((op, m1, m2, m3))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Status {
+    NeedsInput,
+    Output(i64),
+    Halted,
+}
+
+// Everything that can go wrong running a program. Each carries the pc of the
+// instruction that failed.
+#[derive(Debug, PartialEq)]
+pub enum IntcodeError {
+    // The low two digits of an instruction aren't an opcode.
+    UnknownOpcode {
+        pc: usize,
+        code: i64,
+    },
+    // A parameter mode digit isn't 0, 1 or 2.
+    InvalidMode {
+        pc: usize,
+        mode: i64,
+    },
+    // A read, write or jump went to an address below zero.
+    NegativeAddress {
+        pc: usize,
+        address: i64,
+    },
+    // The parameter an instruction writes to was in immediate mode.
+    ImmediateWrite {
+        pc: usize,
+    },
+    // A relative address doesn't fit in a word.
+    OutOfBounds {
+        pc: usize,
+        base: i64,
+        offset: i64,
+    },
+    // Arithmetic, or adjusting the relative base, overflowed a word.
+    Overflow {
+        pc: usize,
+    },
+    // The machine ran as many instructions as its budget allows.
+    BudgetExhausted {
+        pc: usize,
+        executed: u64,
+    },
+    // The machine came back to a state it was in before without any input
+    // or output in between, so it will go round forever. The counts are
+    // how many instructions had run each time it was in that state.
+    InfiniteLoop {
+        pc: usize,
+        first_seen: u64,
+        seen_again: u64,
+    },
+    // A host defined opcode's handler failed.
+    ExtensionFailed {
+        pc: usize,
+        code: i64,
+        message: String,
+    },
+    // An Input instruction ran while running to completion with nothing queued.
+    InputExhausted {
+        pc: usize,
+    },
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntcodeError::UnknownOpcode { pc, code } => {
+                write!(f, "unknown opcode {} at {}", code, pc)
+            }
+            IntcodeError::InvalidMode { pc, mode } => {
+                write!(f, "invalid parameter mode {} at {}", mode, pc)
+            }
+            IntcodeError::NegativeAddress { pc, address } => {
+                write!(f, "negative address {} used at {}", address, pc)
+            }
+            IntcodeError::ImmediateWrite { pc } => {
+                write!(f, "instruction at {} writes to an immediate parameter", pc)
+            }
+            IntcodeError::OutOfBounds { pc, base, offset } => write!(
+                f,
+                "relative address {} + {} at {} is out of bounds",
+                base, offset, pc
+            ),
+            IntcodeError::Overflow { pc } => write!(f, "instruction at {} overflowed", pc),
+            IntcodeError::BudgetExhausted { pc, executed } => write!(
+                f,
+                "stopped at {} after running the budget of {} instructions",
+                pc, executed
+            ),
+            IntcodeError::InfiniteLoop {
+                pc,
+                first_seen,
+                seen_again,
+            } => write!(
+                f,
+                "infinite loop at {}: a {} instruction cycle, first entered after {} instructions",
+                pc,
+                seen_again - first_seen,
+                first_seen
+            ),
+            IntcodeError::ExtensionFailed { pc, code, message } => {
+                write!(f, "opcode {} at {} failed: {}", code, pc, message)
+            }
+            IntcodeError::InputExhausted { pc } => {
+                write!(f, "input instruction at {} found the input queue empty", pc)
+            }
+        }
+    }
+}
+
+impl std::error::Error for IntcodeError {}
+
+// An Intcode computer: the program's memory plus everything needed to run it.
+// Every instruction it executes is reported to its tracer, which does
+// nothing unless one is attached with with_tracer.
+pub struct Machine<T: Tracer = NoTrace> {
+    memory: Memory,
+    pc: usize,
+    relative_base: i64,
+    input: VecDeque<i64>,
+    // everything the program has output so far
+    output: Vec<i64>,
+    // undo log, only kept once record_history is called
+    history: Option<Vec<Undo>>,
+    // instructions run so far, and the most that may be run
+    executed: u64,
+    budget: Option<u64>,
+    // the states seen since the last input or output, only kept once
+    // detect_loops is called
+    seen_states: Option<SeenStates>,
+    // host defined opcodes, by their low two digits
+    extensions: HashMap<i64, Extension>,
+    // decoded instructions, None unless caching is turned on
+    decoded: Option<Decoding>,
+    tracer: T,
+}
+
+impl Machine {
+    pub fn new(program: &[i64]) -> Machine {
+        Machine {
+            memory: Memory::new(program),
+            pc: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            output: vec![],
+            history: None,
+            executed: 0,
+            budget: None,
+            seen_states: None,
+            extensions: HashMap::new(),
+            decoded: None,
+            tracer: NoTrace,
+        }
+    }
+}
+
+impl<T: Tracer> Machine<T> {
+    pub fn with_tracer<U: Tracer>(self, tracer: U) -> Machine<U> {
+        Machine {
+            memory: self.memory,
+            pc: self.pc,
+            relative_base: self.relative_base,
+            input: self.input,
+            output: self.output,
+            history: self.history,
+            executed: self.executed,
+            budget: self.budget,
+            seen_states: self.seen_states,
+            extensions: self.extensions,
+            decoded: self.decoded,
+            tracer,
+        }
+    }
+
+    pub fn tracer(&self) -> &T {
+        &self.tracer
+    }
+
+    pub fn tracer_mut(&mut self) -> &mut T {
+        &mut self.tracer
+    }
+
+    pub fn into_tracer(self) -> T {
+        self.tracer
+    }
+
+    pub fn memory(&self) -> &[i64] {
+        self.memory.as_slice()
+    }
+
+    pub fn into_memory(self) -> Vec<i64> {
+        self.memory.into_vec()
+    }
+
+    // Reads any address, including those past the end of the program.
+    pub fn read(&self, address: usize) -> i64 {
+        self.memory.read(address)
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    pub fn outputs(&self) -> &[i64] {
+        &self.output
+    }
+
+    // Queues a value for a future Input instruction. Each Input consumes one.
+    pub fn provide_input(&mut self, input: i64) {
+        self.input.push_back(input);
+    }
+
+    pub fn provide_inputs<I: IntoIterator<Item = i64>>(&mut self, inputs: I) {
+        self.input.extend(inputs);
+    }
+
+    fn address(&self, value: i64) -> Result<usize, IntcodeError> {
+        value.try_into().map_err(|_| IntcodeError::NegativeAddress {
+            pc: self.pc,
+            address: value,
+        })
+    }
+
+    fn relative_address(&self, offset: i64) -> Result<usize, IntcodeError> {
+        match self.relative_base.checked_add(offset) {
+            Some(address) => self.address(address),
+            None => Err(IntcodeError::OutOfBounds {
+                pc: self.pc,
+                base: self.relative_base,
+                offset,
+            }),
+        }
+    }
+
+    fn get_arg(&self, param: i64, mode: ArgMode) -> Result<i64, IntcodeError> {
+        match mode {
+            ArgMode::Position => Ok(self.memory.read(self.address(param)?)),
+            ArgMode::Immediate => Ok(param),
+            ArgMode::Relative => Ok(self.memory.read(self.relative_address(param)?)),
+        }
+    }
+
+    // Parameters that are written to name an address, so they can never be immediate.
+    fn get_dest(&self, param: i64, mode: ArgMode) -> Result<usize, IntcodeError> {
+        match mode {
+            ArgMode::Position => self.address(param),
+            ArgMode::Immediate => Err(IntcodeError::ImmediateWrite { pc: self.pc }),
+            ArgMode::Relative => self.relative_address(param),
+        }
+    }
+
+    fn overflowed(&self) -> IntcodeError {
+        IntcodeError::Overflow { pc: self.pc }
+    }
+
+    // Executes a single instruction. Returns a status when the machine
+    // has something to report, leaving pc on the Input or Halt instruction
+    // that stopped it so that a later call picks up where this one left off.
+    pub fn step(&mut self) -> Result<Option<Status>, IntcodeError> {
+        self.check_budget()?;
+
+        let pc = self.pc;
+        let relative_base = self.relative_base;
+        let Decoded {
+            opcode,
+            modes,
+            params,
+        } = match self.decode(pc) {
+            Ok(decoded) => decoded,
+            Err(IntcodeError::UnknownOpcode { code, .. })
+                if self.extensions.contains_key(&(code % 100)) =>
+            {
+                return self.step_extension(code % 100);
+            }
+            Err(err) => return Err(err),
+        };
+        // Read every parameter up front. args holds the value of each read
+        // parameter, and the address for the one that is written to.
+        let mut args = [0; 3];
+        let mut dest = 0;
+        for (i, mode) in modes.iter().enumerate().take(opcode.arity()) {
+            if opcode.write_param() == Some(i) {
+                dest = self.get_dest(params[i], *mode)?;
+                args[i] = dest as i64;
+            } else {
+                args[i] = self.get_arg(params[i], *mode)?;
+            }
+        }
+        let [a, b, _] = args;
+
+        let mut write = None;
+        let mut status = None;
+        let mut consumed = None;
+        match opcode {
+            OpCode::Add => {
+                // Addition
+                let sum = a.checked_add(b).ok_or_else(|| self.overflowed())?;
+                write = Some((dest, sum));
+
+                self.pc += 4
+            }
+            OpCode::Mult => {
+                // multplication
+                let product = a.checked_mul(b).ok_or_else(|| self.overflowed())?;
+                write = Some((dest, product));
+
+                self.pc += 4
+            }
+            OpCode::Input => {
+                // store input, or wait for the host to provide some
+                let input = match self.input.pop_front() {
+                    Some(input) => input,
+                    None => return Ok(Some(Status::NeedsInput)),
+                };
+                write = Some((dest, input));
+                consumed = Some(input);
+
+                self.pc += 2
+            }
+            OpCode::Output => {
+                // send output
+                self.output.push(a);
+                status = Some(Status::Output(a));
+
+                self.pc += 2
+            }
+            OpCode::JumpIfTrue => {
+                if a != 0 {
+                    self.pc = self.address(b)?;
+                } else {
+                    self.pc += 3;
+                }
+            }
+            OpCode::JumpIfFalse => {
+                if a == 0 {
+                    self.pc = self.address(b)?;
+                } else {
+                    self.pc += 3;
+                }
+            }
+            OpCode::LessThan => {
+                write = Some((dest, if a < b { 1 } else { 0 }));
+
+                self.pc += 4
+            }
+            OpCode::Equals => {
+                write = Some((dest, if a == b { 1 } else { 0 }));
+
+                self.pc += 4
+            }
+            OpCode::AdjustRelativeBase => {
+                self.relative_base = self
+                    .relative_base
+                    .checked_add(a)
+                    .ok_or_else(|| self.overflowed())?;
+
+                self.pc += 2
+            }
+            OpCode::Halt => {
+                // exit
+                status = Some(Status::Halted);
+            }
+        }
+
+        let output = opcode == OpCode::Output;
+        self.commit(
+            pc,
+            relative_base,
+            write,
+            consumed,
+            output,
+            opcode == OpCode::Halt,
+        )?;
+
+        self.tracer.trace(&Step {
+            pc,
+            opcode,
+            modes,
+            params,
+            args,
+            write,
+        });
+
+        Ok(status)
+    }
+
+    // Stores a value, and forgets any cached decoding of it.
+    fn write(&mut self, address: usize, value: i64) {
+        self.memory.write(address, value);
+        if let Some(decoding) = &mut self.decoded {
+            decoding.written(address);
+        }
+    }
+
+    // Applies an executed instruction's write and does the bookkeeping for
+    // it: the undo log, the instruction count and loop detection. pc and
+    // relative_base are the values from before the instruction ran.
+    fn commit(
+        &mut self,
+        pc: usize,
+        relative_base: i64,
+        write: Option<(usize, i64)>,
+        consumed: Option<i64>,
+        output: bool,
+        halted: bool,
+    ) -> Result<(), IntcodeError> {
+        let overwritten = write.map(|(address, _)| (address, self.memory.read(address)));
+        if let Some(history) = &mut self.history {
+            if !halted {
+                history.push(Undo {
+                    pc,
+                    relative_base,
+                    write: overwritten,
+                    input: consumed,
+                    output,
+                    executed: self.executed,
+                });
+            }
+        }
+
+        if let Some((address, value)) = write {
+            self.log_write(address);
+            self.write(address, value);
+        }
+
+        self.executed += 1;
+        if !halted {
+            self.check_loop(consumed.is_some() || output)?;
+        }
+
+        Ok(())
+    }
+
+    // Runs until the machine needs input, produces an output, or halts.
+    pub fn resume(&mut self) -> Result<Status, IntcodeError> {
+        loop {
+            if let Some(status) = self.step()? {
+                return Ok(status);
+            }
+        }
+    }
+
+    // Runs until the program halts, returning everything it output.
+    // Input comes from the queue, it is an error for the queue to run dry.
+    pub fn run(&mut self) -> Result<Vec<i64>, IntcodeError> {
+        let start = self.output.len();
+
+        loop {
+            match self.resume()? {
+                Status::Output(_) => {}
+                Status::NeedsInput => return Err(IntcodeError::InputExhausted { pc: self.pc }),
+                Status::Halted => break,
+            }
+        }
+
+        Ok(self.output[start..].to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_code_test() {
+        assert_eq!(
+            parse_code(0, 1101),
+            Ok((
+                OpCode::Add,
+                ArgMode::Immediate,
+                ArgMode::Immediate,
+                ArgMode::Position
+            ))
+        );
+        assert_eq!(
+            parse_code(0, 1102),
+            Ok((
+                OpCode::Mult,
+                ArgMode::Immediate,
+                ArgMode::Immediate,
+                ArgMode::Position
+            ))
+        );
+        assert_eq!(
+            parse_code(0, 10101),
+            Ok((
+                OpCode::Add,
+                ArgMode::Immediate,
+                ArgMode::Position,
+                ArgMode::Immediate
+            ))
+        );
+    }
+
+    #[test]
+    fn machine_keeps_memory() {
+        let mut machine = Machine::new(&[3, 0, 4, 0, 99]);
+        machine.provide_input(42);
+
+        assert_eq!(machine.run(), Ok(vec![42]));
+        assert_eq!(machine.memory(), &[42, 0, 4, 0, 99]);
+    }
+
+    #[test]
+    fn resume_yields() {
+        let mut machine = Machine::new(&fixtures::DOUBLER);
+
+        assert_eq!(machine.resume(), Ok(Status::NeedsInput));
+        assert_eq!(machine.resume(), Ok(Status::NeedsInput));
+
+        machine.provide_input(21);
+        assert_eq!(machine.resume(), Ok(Status::Output(42)));
+        assert_eq!(machine.resume(), Ok(Status::NeedsInput));
+
+        machine.provide_input(4);
+        assert_eq!(machine.resume(), Ok(Status::Output(8)));
+
+        machine.provide_input(0);
+        assert_eq!(machine.resume(), Ok(Status::Halted));
+        assert_eq!(machine.resume(), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn input_queue() {
+        // adds together two inputs
+        let program = [3, 11, 3, 12, 1, 11, 12, 11, 4, 11, 99, 0, 0];
+
+        let mut machine = Machine::new(&program);
+        machine.provide_inputs(vec![30, 12]);
+        assert_eq!(machine.run(), Ok(vec![42]));
+
+        let mut machine = Machine::new(&program);
+        machine.provide_input(30);
+        assert_eq!(machine.run(), Err(IntcodeError::InputExhausted { pc: 2 }));
+    }
+
+    #[test]
+    fn relative_mode() {
+        // moves the relative base past the code, then adds 5 to an input
+        let mut machine = Machine::new(&[109, 11, 203, 1, 21201, 1, 5, 2, 204, 2, 99, 0, 0, 0]);
+        machine.provide_input(37);
+
+        assert_eq!(machine.run(), Ok(vec![42]));
+        assert_eq!(machine.relative_base, 11);
+        assert_eq!(machine.memory()[11..], [0, 37, 42]);
+    }
+
+    #[test]
+    fn big_memory() {
+        // quine, needs memory past the end of the program
+        let quine = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        assert_eq!(Machine::new(&quine).run(), Ok(quine));
+
+        assert_eq!(
+            Machine::new(&[1102, 34915192, 34915192, 7, 4, 7, 99, 0]).run(),
+            Ok(vec![1219070632396864])
+        );
+        assert_eq!(
+            Machine::new(&[104, 1125899906842624, 99]).run(),
+            Ok(vec![1125899906842624])
+        );
+
+        assert_eq!(
+            parse_code(0, 21202),
+            Ok((
+                OpCode::Mult,
+                ArgMode::Relative,
+                ArgMode::Immediate,
+                ArgMode::Relative
+            ))
+        );
+        assert_eq!(
+            parse_code(0, 209),
+            Ok((
+                OpCode::AdjustRelativeBase,
+                ArgMode::Relative,
+                ArgMode::Position,
+                ArgMode::Position
+            ))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Machine::new(&[1, 0, 0, 0, 42]).run(),
+            Err(IntcodeError::UnknownOpcode { pc: 4, code: 42 })
+        );
+        assert_eq!(
+            Machine::new(&[301, 0, 0, 0, 99]).run(),
+            Err(IntcodeError::InvalidMode { pc: 0, mode: 3 })
+        );
+        assert_eq!(
+            Machine::new(&[1, -1, 0, 0, 99]).run(),
+            Err(IntcodeError::NegativeAddress { pc: 0, address: -1 })
+        );
+        assert_eq!(
+            Machine::new(&[1105, 1, -7]).run(),
+            Err(IntcodeError::NegativeAddress { pc: 0, address: -7 })
+        );
+        assert_eq!(
+            Machine::new(&[10001, 0, 0, 0, 99]).run(),
+            Err(IntcodeError::ImmediateWrite { pc: 0 })
+        );
+        assert_eq!(
+            Machine::new(&[109, i64::MAX, 204, 1, 99]).run(),
+            Err(IntcodeError::OutOfBounds {
+                pc: 2,
+                base: i64::MAX,
+                offset: 1
+            })
+        );
+        assert_eq!(
+            Machine::new(&[1102, i64::MAX, 2, 0, 99]).run(),
+            Err(IntcodeError::Overflow { pc: 0 })
+        );
+        assert_eq!(
+            IntcodeError::UnknownOpcode { pc: 4, code: 42 }.to_string(),
+            "unknown opcode 42 at 4"
+        );
+    }
+}
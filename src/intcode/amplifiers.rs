@@ -0,0 +1,220 @@
+// A chain of amplifiers, each running its own copy of the same program.
+// Every amplifier reads its phase setting first, then a signal, and outputs
+// the signal for the next amplifier in line.
+use super::{IntcodeError, Machine, Status};
+
+fn start_amplifiers(program: &[i64], phases: &[i64]) -> Vec<Machine> {
+    phases
+        .iter()
+        .map(|phase| {
+            let mut amplifier = Machine::new(program);
+            amplifier.provide_input(*phase);
+            amplifier
+        })
+        .collect()
+}
+
+// Feeds signal to an amplifier and waits for its answer. None means it
+// halted instead.
+fn amplify(amplifier: &mut Machine, signal: i64) -> Result<Option<i64>, IntcodeError> {
+    amplifier.provide_input(signal);
+    match amplifier.resume()? {
+        Status::Output(value) => Ok(Some(value)),
+        Status::Halted => Ok(None),
+        Status::NeedsInput => Err(IntcodeError::InputExhausted { pc: amplifier.pc() }),
+    }
+}
+
+// Passes a 0 signal through the amplifiers once, returning what comes out
+// of the last one.
+pub fn run_chain(program: &[i64], phases: &[i64]) -> Result<i64, IntcodeError> {
+    let mut signal = 0;
+    for mut amplifier in start_amplifiers(program, phases) {
+        if let Some(output) = amplify(&mut amplifier, signal)? {
+            signal = output;
+        }
+    }
+
+    Ok(signal)
+}
+
+// Like run_chain, but the last amplifier's output loops back to the first.
+// Every amplifier keeps its state between signals, and the loop runs until
+// they have all halted. A signal sent to an amplifier that has halted is
+// lost. Returns the last signal the last amplifier sent.
+pub fn run_feedback(program: &[i64], phases: &[i64]) -> Result<i64, IntcodeError> {
+    let mut amplifiers = start_amplifiers(program, phases);
+    let mut halted = vec![false; amplifiers.len()];
+    let last = amplifiers.len().saturating_sub(1);
+
+    let mut signal = Some(0);
+    let mut thruster = 0;
+    while halted.contains(&false) {
+        for (i, amplifier) in amplifiers.iter_mut().enumerate() {
+            if halted[i] {
+                signal = None;
+                continue;
+            }
+
+            if let Some(value) = signal.take() {
+                amplifier.provide_input(value);
+            }
+            match amplifier.resume()? {
+                Status::Output(value) => {
+                    signal = Some(value);
+                    if i == last {
+                        thruster = value;
+                    }
+                }
+                Status::Halted => halted[i] = true,
+                Status::NeedsInput => {
+                    return Err(IntcodeError::InputExhausted { pc: amplifier.pc() })
+                }
+            }
+        }
+    }
+
+    Ok(thruster)
+}
+
+// Every ordering of values.
+fn permutations(values: &[i64]) -> Vec<Vec<i64>> {
+    if values.len() <= 1 {
+        return vec![values.to_vec()];
+    }
+
+    let mut all = vec![];
+    for (i, first) in values.iter().enumerate() {
+        let mut rest = values.to_vec();
+        rest.remove(i);
+        for mut permutation in permutations(&rest) {
+            permutation.insert(0, *first);
+            all.push(permutation);
+        }
+    }
+
+    all
+}
+
+fn best_phases<F>(settings: &[i64], run: F) -> Result<(i64, Vec<i64>), IntcodeError>
+where
+    F: Fn(&[i64]) -> Result<i64, IntcodeError>,
+{
+    let mut best: Option<(i64, Vec<i64>)> = None;
+    for phases in permutations(settings) {
+        let signal = run(&phases)?;
+        if best.as_ref().is_none_or(|(max, _)| signal > *max) {
+            best = Some((signal, phases));
+        }
+    }
+
+    Ok(best.unwrap())
+}
+
+// Tries every ordering of the phase settings 0 through 4, returning the
+// highest thruster signal and the phases that made it.
+pub fn max_thruster_signal(program: &[i64]) -> Result<(i64, Vec<i64>), IntcodeError> {
+    best_phases(&[0, 1, 2, 3, 4], |phases| run_chain(program, phases))
+}
+
+// The same search for feedback loops, which use phase settings 5 through 9.
+pub fn max_feedback_signal(program: &[i64]) -> Result<(i64, Vec<i64>), IntcodeError> {
+    best_phases(&[5, 6, 7, 8, 9], |phases| run_feedback(program, phases))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    #[test]
+    fn chains() {
+        let program = vec![
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ];
+        assert_eq!(run_chain(&program, &[4, 3, 2, 1, 0]), Ok(43210));
+        assert_eq!(
+            max_thruster_signal(&program),
+            Ok((43210, vec![4, 3, 2, 1, 0]))
+        );
+
+        let program = vec![
+            3, 23, 3, 24, 1002, 24, 10, 24, 1002, 23, -1, 23, 101, 5, 23, 23, 1, 24, 23, 23, 4, 23,
+            99, 0, 0,
+        ];
+        assert_eq!(
+            max_thruster_signal(&program),
+            Ok((54321, vec![0, 1, 2, 3, 4]))
+        );
+
+        let program = vec![
+            3, 31, 3, 32, 1002, 32, 10, 32, 1001, 31, -2, 31, 1007, 31, 0, 33, 1002, 33, 7, 33, 1,
+            33, 31, 31, 1, 32, 31, 31, 4, 31, 99, 0, 0, 0,
+        ];
+        assert_eq!(
+            max_thruster_signal(&program),
+            Ok((65210, vec![1, 0, 4, 3, 2]))
+        );
+    }
+
+    #[test]
+    fn feedback() {
+        let program = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+        assert_eq!(run_feedback(&program, &[9, 8, 7, 6, 5]), Ok(139629729));
+        assert_eq!(
+            max_feedback_signal(&program),
+            Ok((139629729, vec![9, 8, 7, 6, 5]))
+        );
+
+        let program = vec![
+            3, 52, 1001, 52, -5, 52, 3, 53, 1, 52, 56, 54, 1007, 54, 5, 55, 1005, 55, 26, 1001, 54,
+            -5, 54, 1105, 1, 12, 1, 53, 54, 53, 1008, 54, 0, 55, 1001, 55, 1, 55, 2, 53, 55, 53, 4,
+            53, 1001, 56, -1, 56, 1005, 56, 6, 99, 0, 0, 0, 0, 10,
+        ];
+        assert_eq!(
+            max_feedback_signal(&program),
+            Ok((18216, vec![9, 7, 8, 5, 6]))
+        );
+    }
+
+    #[test]
+    fn halting_at_different_times() {
+        // adds one to each signal, for as many signals as its phase says
+        let program = assemble(
+            "
+                    IN [count]
+            loop:   IN [signal]
+                    ADD [signal] #1 [signal]
+                    OUT [signal]
+                    ADD [count] #-1 [count]
+                    JT [count] #loop
+                    HLT
+            count:  DATA 0
+            signal: DATA 0
+            ",
+        )
+        .unwrap();
+
+        // the last amplifier stops after one signal, the rest go round again
+        // and then halt without wanting more
+        assert_eq!(run_feedback(&program, &[2, 2, 2, 2, 1]), Ok(5));
+
+        // once the first has stopped, the second never gets its signal
+        assert_eq!(
+            run_feedback(&program, &[1, 2, 2, 2, 2]),
+            Err(IntcodeError::InputExhausted { pc: 2 })
+        );
+    }
+
+    #[test]
+    fn all_permutations() {
+        let all = permutations(&[1, 2, 3]);
+
+        assert_eq!(all.len(), 6);
+        assert_eq!(all[0], vec![1, 2, 3]);
+        assert_eq!(all[5], vec![3, 2, 1]);
+    }
+}
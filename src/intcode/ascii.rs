@@ -0,0 +1,163 @@
+// Text I/O for programs that speak ASCII. Outputs in 0..=127 are characters
+// and are written as they are. Anything else is a number, the answer to the
+// puzzle more often than not, and is written in decimal on a line of its
+// own. Input is read a line at a time and given to the program as character
+// codes, ending with a newline.
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use super::{IntcodeError, Machine, Status, Tracer};
+
+#[derive(Debug)]
+pub enum AsciiError {
+    Io(io::Error),
+    Intcode(IntcodeError),
+    // a character in a line of input that isn't ASCII
+    NotAscii(char),
+    // the program wanted more input after the last line
+    InputEnded,
+}
+
+impl fmt::Display for AsciiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsciiError::Io(err) => write!(f, "{}", err),
+            AsciiError::Intcode(err) => write!(f, "{}", err),
+            AsciiError::NotAscii(c) => write!(f, "{:?} isn't an ASCII character", c),
+            AsciiError::InputEnded => write!(f, "the program wanted more input"),
+        }
+    }
+}
+
+impl std::error::Error for AsciiError {}
+
+impl From<io::Error> for AsciiError {
+    fn from(err: io::Error) -> AsciiError {
+        AsciiError::Io(err)
+    }
+}
+
+impl From<IntcodeError> for AsciiError {
+    fn from(err: IntcodeError) -> AsciiError {
+        AsciiError::Intcode(err)
+    }
+}
+
+// Writes a value the program output, as a character if it is one.
+pub fn write_value<W: Write>(out: &mut W, value: i64) -> io::Result<()> {
+    if (0..=127).contains(&value) {
+        out.write_all(&[value as u8])
+    } else {
+        writeln!(out, "{}", value)
+    }
+}
+
+impl<T: Tracer> Machine<T> {
+    // Queues a line of text as input, followed by a newline.
+    pub fn provide_line(&mut self, line: &str) -> Result<(), AsciiError> {
+        if let Some(c) = line.chars().find(|c| !c.is_ascii()) {
+            return Err(AsciiError::NotAscii(c));
+        }
+
+        self.provide_inputs(line.bytes().map(i64::from));
+        self.provide_input(10);
+
+        Ok(())
+    }
+
+    // Runs the program until it halts, writing its output to out and
+    // reading a line from input whenever it runs out of input.
+    pub fn play<R: BufRead, W: Write>(
+        &mut self,
+        mut input: R,
+        mut out: W,
+    ) -> Result<(), AsciiError> {
+        loop {
+            match self.resume()? {
+                Status::Output(value) => write_value(&mut out, value)?,
+                Status::NeedsInput => {
+                    out.flush()?;
+
+                    let mut line = String::new();
+                    if input.read_line(&mut line)? == 0 {
+                        return Err(AsciiError::InputEnded);
+                    }
+                    let line = line.trim_end_matches('\n').trim_end_matches('\r');
+                    self.provide_line(line)?;
+                }
+                Status::Halted => {
+                    out.flush()?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+// Plays a program from the terminal.
+pub fn play_stdin<T: Tracer>(machine: &mut Machine<T>) -> Result<(), AsciiError> {
+    let stdin = io::stdin();
+    machine.play(stdin.lock(), io::stdout())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    // Prompts for a line, echoes it back, then outputs a thousand times its
+    // length as a number. Once the line is just "q" it stops.
+    const ECHO: &str = "
+        start:  OUT #62         ; '>'
+                OUT #32
+                ADD #0 #0 [n]
+        read:   IN [c]
+                OUT [c]
+                ADD [n] #1 [n]
+                EQ [c] #10 [t]
+                JF [t] #read
+                MUL [n] #1000 [big]
+                OUT [big]
+                EQ [n] #2 [t]
+                JF [t] #start
+                HLT
+        c:      DATA 0
+        n:      DATA 0
+        t:      DATA 0
+        big:    DATA 0
+    ";
+
+    fn play(input: &str) -> (Result<(), AsciiError>, String) {
+        let mut machine = Machine::new(&assemble(ECHO).unwrap());
+        let mut out = vec![];
+        let result = machine.play(input.as_bytes(), &mut out);
+
+        (result, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn echo() {
+        let (result, out) = play("hello\r\nq\n");
+        assert!(result.is_ok());
+        assert_eq!(out, "> hello\n6000\n> q\n2000\n");
+    }
+
+    #[test]
+    fn bad_input() {
+        let (result, out) = play("hi\n");
+        assert!(matches!(result, Err(AsciiError::InputEnded)));
+        assert_eq!(out, "> hi\n3000\n> ");
+
+        let (result, _) = play("héllo\n");
+        assert!(matches!(result, Err(AsciiError::NotAscii('é'))));
+    }
+
+    #[test]
+    fn values() {
+        let mut out = vec![];
+        for value in &[72, 105, 10, 128, -1, 0] {
+            write_value(&mut out, *value).unwrap();
+        }
+        assert_eq!(out, b"Hi\n128\n-1\n\0");
+    }
+}
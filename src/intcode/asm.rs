@@ -0,0 +1,334 @@
+// An assembler for the listing syntax the disassembler prints, plus labels.
+//
+//         IN [input]          ; position mode reads an address
+//         JF [input] #done    ; immediate mode is a plain value
+//         ADD [rb+1] #1 [rb-2]
+// done:   HLT
+// input:  DATA 0 1 2 done
+//
+// Operands and DATA words can name a label instead of a number, and ';'
+// starts a comment that runs to the end of the line.
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{ArgMode, OpCode};
+
+#[derive(Debug, PartialEq)]
+pub enum AsmError {
+    UnknownMnemonic {
+        line: usize,
+        mnemonic: String,
+    },
+    UndefinedLabel {
+        line: usize,
+        label: String,
+    },
+    DuplicateLabel {
+        line: usize,
+        label: String,
+    },
+    BadOperand {
+        line: usize,
+        operand: String,
+    },
+    WrongArity {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsmError::UnknownMnemonic { line, mnemonic } => {
+                write!(f, "line {}: unknown mnemonic {}", line, mnemonic)
+            }
+            AsmError::UndefinedLabel { line, label } => {
+                write!(f, "line {}: undefined label {}", line, label)
+            }
+            AsmError::DuplicateLabel { line, label } => {
+                write!(f, "line {}: label {} is already defined", line, label)
+            }
+            AsmError::BadOperand { line, operand } => {
+                write!(f, "line {}: can't parse operand {}", line, operand)
+            }
+            AsmError::WrongArity {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected {} operands but found {}",
+                line, expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+// A number, or a label that resolves to one once every address is known.
+#[derive(Debug)]
+enum Value {
+    Number(i64),
+    Label(String),
+}
+
+// One word of output, waiting on label resolution. Line numbers are kept
+// for reporting undefined labels.
+struct Word {
+    line: usize,
+    value: Value,
+    // [rb-label] stores the label's address negated
+    negate: bool,
+    // added to the resolved value, used to fold instruction modes in
+    base: i64,
+}
+
+fn parse_value(line: usize, text: &str) -> Result<Value, AsmError> {
+    if let Ok(number) = text.parse::<i64>() {
+        return Ok(Value::Number(number));
+    }
+
+    let mut chars = text.chars();
+    let starts_well = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_');
+    if starts_well && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Ok(Value::Label(text.to_string()))
+    } else {
+        Err(AsmError::BadOperand {
+            line,
+            operand: text.to_string(),
+        })
+    }
+}
+
+fn parse_operand(line: usize, text: &str) -> Result<(ArgMode, Value), AsmError> {
+    let bad = || AsmError::BadOperand {
+        line,
+        operand: text.to_string(),
+    };
+
+    if let Some(rest) = text.strip_prefix('#') {
+        return Ok((ArgMode::Immediate, parse_value(line, rest)?));
+    }
+
+    let inner = text
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .ok_or_else(bad)?;
+    if let Some(offset) = inner.strip_prefix("rb+") {
+        Ok((ArgMode::Relative, parse_value(line, offset)?))
+    } else if let Some(offset) = inner.strip_prefix("rb-") {
+        // the sign is put back when the word is resolved
+        Ok((ArgMode::Relative, parse_value(line, offset)?))
+    } else {
+        Ok((ArgMode::Position, parse_value(line, inner)?))
+    }
+}
+
+fn find_opcode(mnemonic: &str) -> Option<OpCode> {
+    let upper = mnemonic.to_ascii_uppercase();
+    OpCode::ALL
+        .iter()
+        .cloned()
+        .find(|opcode| opcode.mnemonic() == upper)
+}
+
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut words: Vec<Word> = vec![];
+    let mut labels: HashMap<String, i64> = HashMap::new();
+
+    for (i, raw) in source.lines().enumerate() {
+        let line = i + 1;
+        let mut text = raw.split(';').next().unwrap().trim();
+
+        // any number of labels can come before the instruction
+        while let Some(colon) = text.find(':') {
+            let label = text[..colon].trim();
+            match parse_value(line, label)? {
+                Value::Label(label) => {
+                    if labels.insert(label.clone(), words.len() as i64).is_some() {
+                        return Err(AsmError::DuplicateLabel { line, label });
+                    }
+                }
+                Value::Number(_) => {
+                    return Err(AsmError::BadOperand {
+                        line,
+                        operand: label.to_string(),
+                    })
+                }
+            }
+            text = text[colon + 1..].trim();
+        }
+
+        let mut tokens = text.split_whitespace();
+        let mnemonic = match tokens.next() {
+            Some(mnemonic) => mnemonic,
+            None => continue,
+        };
+        let operands: Vec<&str> = tokens.collect();
+
+        if mnemonic.eq_ignore_ascii_case("DATA") {
+            for operand in operands {
+                words.push(Word {
+                    line,
+                    value: parse_value(line, operand)?,
+                    negate: false,
+                    base: 0,
+                });
+            }
+            continue;
+        }
+
+        let opcode = find_opcode(mnemonic).ok_or_else(|| AsmError::UnknownMnemonic {
+            line,
+            mnemonic: mnemonic.to_string(),
+        })?;
+        if operands.len() != opcode.arity() {
+            return Err(AsmError::WrongArity {
+                line,
+                expected: opcode.arity(),
+                found: operands.len(),
+            });
+        }
+
+        let instruction_i = words.len();
+        words.push(Word {
+            line,
+            value: Value::Number(opcode.code()),
+            negate: false,
+            base: 0,
+        });
+
+        let mut scale = 100;
+        for operand in operands {
+            let (mode, value) = parse_operand(line, operand)?;
+            words[instruction_i].base += mode.code() * scale;
+            scale *= 10;
+
+            words.push(Word {
+                line,
+                value,
+                negate: operand.starts_with("[rb-"),
+                base: 0,
+            });
+        }
+    }
+
+    words
+        .into_iter()
+        .map(|word| {
+            let value = match word.value {
+                Value::Number(number) => number,
+                Value::Label(label) => match labels.get(&label) {
+                    Some(address) => *address,
+                    None => {
+                        return Err(AsmError::UndefinedLabel {
+                            line: word.line,
+                            label,
+                        })
+                    }
+                },
+            };
+
+            if word.negate {
+                Ok(word.base - value)
+            } else {
+                Ok(word.base + value)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::disasm::listing;
+
+    #[test]
+    fn labels_and_data() {
+        let source = "
+                    IN [input]
+                    JF [input] [target]     ; skip the add for a zero input
+                    ADD [result] [one] [result]
+            print:  OUT [result]
+                    HLT
+            input:  DATA -1
+            result: DATA 0
+            one:    DATA 1
+            target: DATA print
+        ";
+
+        assert_eq!(
+            assemble(source),
+            Ok(vec![
+                3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9
+            ])
+        );
+    }
+
+    #[test]
+    fn modes() {
+        assert_eq!(
+            assemble("ADD [rb+1] #-2 [rb-3]\nJT #1 #end\nend: hlt"),
+            Ok(vec![21201, 1, -2, -3, 1105, 1, 7, 99])
+        );
+
+        // anything the disassembler prints, short of DATA for bad words
+        let program = vec![109, 11, 203, 1, 21201, 1, 5, 2, 204, 2, 99];
+        let source: String = listing(&program)
+            .lines()
+            .map(|line| format!("{}\n", line.split_once(':').unwrap().1))
+            .collect();
+        assert_eq!(assemble(&source), Ok(program));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            assemble("IN [0]\nDIV [0] [1] [2]"),
+            Err(AsmError::UnknownMnemonic {
+                line: 2,
+                mnemonic: "DIV".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("IN [0]\nOUT [0]\nJT #1 #nowhere"),
+            Err(AsmError::UndefinedLabel {
+                line: 3,
+                label: "nowhere".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("ADD [0] [1]"),
+            Err(AsmError::WrongArity {
+                line: 1,
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            assemble("OUT 5"),
+            Err(AsmError::BadOperand {
+                line: 1,
+                operand: "5".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("a: HLT\na: HLT"),
+            Err(AsmError::DuplicateLabel {
+                line: 2,
+                label: "a".to_string()
+            })
+        );
+        assert_eq!(
+            AsmError::UndefinedLabel {
+                line: 3,
+                label: "nowhere".to_string()
+            }
+            .to_string(),
+            "line 3: undefined label nowhere"
+        );
+    }
+}
@@ -0,0 +1,346 @@
+// Control flow graphs, worked out from a program without running it. The
+// program is split into basic blocks, runs of instructions that always
+// execute one after the other, which end at a jump, a halt, or just before
+// an instruction something else jumps to.
+//
+// Only immediate mode jump targets are known statically. A jump whose
+// target comes from memory gets an edge to Target::Unknown, and nothing is
+// followed from it. Self modifying programs are taken as they are written,
+// so an instruction that is only patched into place at run time shows up as
+// an invalid target.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::disasm;
+use super::{parse_code, ArgMode, OpCode};
+
+// A decoded instruction and where it sits in the program.
+pub struct Instruction {
+    pub address: usize,
+    pub opcode: OpCode,
+    pub modes: [ArgMode; 3],
+    pub params: Vec<i64>,
+    // the address just past its parameters
+    pub next: usize,
+    // how it reads in a listing
+    pub text: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    Block(usize),
+    // an address with no valid instruction at it
+    Invalid(usize),
+    // an address read from memory when the jump runs
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EdgeKind {
+    // on to the following instruction
+    Next,
+    Taken,
+    NotTaken,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Edge {
+    pub kind: EdgeKind,
+    pub to: Target,
+}
+
+impl Instruction {
+    fn decode(program: &[i64], address: usize) -> Option<Instruction> {
+        let line = disasm::decode(program, address)?;
+        let (opcode, m1, m2, m3) = parse_code(address, program[address]).ok()?;
+
+        Some(Instruction {
+            address,
+            opcode,
+            modes: [m1, m2, m3],
+            params: line.words[1..].to_vec(),
+            next: address + line.words.len(),
+            text: line.text,
+        })
+    }
+
+    pub fn is_jump(&self) -> bool {
+        self.opcode == OpCode::JumpIfTrue || self.opcode == OpCode::JumpIfFalse
+    }
+
+    // Where control can go after this instruction, as addresses, or None
+    // for a jump to an address that isn't known. A jump on an immediate
+    // condition only goes the one way.
+    pub fn successors(&self) -> Vec<(EdgeKind, Option<usize>)> {
+        if self.opcode == OpCode::Halt {
+            return vec![];
+        }
+        if !self.is_jump() {
+            return vec![(EdgeKind::Next, Some(self.next))];
+        }
+
+        let target = match (self.modes[1], self.params[1]) {
+            (ArgMode::Immediate, target) if target >= 0 => Some(target as usize),
+            _ => None,
+        };
+        let taken = (self.params[0] != 0) == (self.opcode == OpCode::JumpIfTrue);
+        match self.modes[0] {
+            ArgMode::Immediate if taken => vec![(EdgeKind::Taken, target)],
+            ArgMode::Immediate => vec![(EdgeKind::NotTaken, Some(self.next))],
+            _ => vec![
+                (EdgeKind::Taken, target),
+                (EdgeKind::NotTaken, Some(self.next)),
+            ],
+        }
+    }
+}
+
+// Every instruction reachable from entry by following the program's control
+// flow, by address.
+pub fn reachable(program: &[i64], entry: usize) -> BTreeMap<usize, Instruction> {
+    let mut found = BTreeMap::new();
+    let mut pending = vec![entry];
+
+    while let Some(address) = pending.pop() {
+        if address >= program.len() || found.contains_key(&address) {
+            continue;
+        }
+        let instruction = match Instruction::decode(program, address) {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+
+        for (_, successor) in instruction.successors() {
+            pending.extend(successor);
+        }
+        found.insert(address, instruction);
+    }
+
+    found
+}
+
+pub struct Block {
+    pub instructions: Vec<Instruction>,
+    pub edges: Vec<Edge>,
+}
+
+pub struct Cfg {
+    // by the address of their first instruction
+    pub blocks: BTreeMap<usize, Block>,
+}
+
+impl Cfg {
+    pub fn build(program: &[i64], entry: usize) -> Cfg {
+        let mut instructions = reachable(program, entry);
+
+        // blocks start at the entry and wherever a jump can land
+        let mut leaders = BTreeSet::new();
+        leaders.insert(entry);
+        for instruction in instructions.values().filter(|i| i.is_jump()) {
+            for (_, successor) in instruction.successors() {
+                leaders.extend(successor);
+            }
+        }
+        let leaders: Vec<usize> = leaders
+            .into_iter()
+            .filter(|leader| instructions.contains_key(leader))
+            .collect();
+
+        let mut blocks = BTreeMap::new();
+        for &leader in &leaders {
+            let mut block = vec![];
+            let mut address = leader;
+            loop {
+                let instruction = instructions.remove(&address).unwrap();
+                let successors = instruction.successors();
+                address = instruction.next;
+                block.push(instruction);
+
+                let falls_through = successors == vec![(EdgeKind::Next, Some(address))];
+                if !falls_through
+                    || leaders.binary_search(&address).is_ok()
+                    || !instructions.contains_key(&address)
+                {
+                    break;
+                }
+            }
+
+            let edges = block
+                .last()
+                .unwrap()
+                .successors()
+                .into_iter()
+                .map(|(kind, to)| Edge {
+                    kind,
+                    to: match to {
+                        Some(address) if leaders.binary_search(&address).is_ok() => {
+                            Target::Block(address)
+                        }
+                        Some(address) => Target::Invalid(address),
+                        None => Target::Unknown,
+                    },
+                })
+                .collect();
+            blocks.insert(
+                leader,
+                Block {
+                    instructions: block,
+                    edges,
+                },
+            );
+        }
+
+        Cfg { blocks }
+    }
+
+    // The graph in Graphviz's DOT language, one box per block.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph intcode {\n");
+        dot.push_str("    node [shape=box, fontname=monospace];\n");
+
+        let mut invalid = BTreeSet::new();
+        let mut unknown = false;
+        for (start, block) in &self.blocks {
+            let mut label = String::new();
+            for instruction in &block.instructions {
+                write!(label, "{}: {}\\l", instruction.address, instruction.text).unwrap();
+            }
+            writeln!(dot, "    b{} [label=\"{}\"];", start, label).unwrap();
+
+            for edge in &block.edges {
+                let to = match edge.to {
+                    Target::Block(address) => format!("b{}", address),
+                    Target::Invalid(address) => {
+                        invalid.insert(address);
+                        format!("invalid{}", address)
+                    }
+                    Target::Unknown => {
+                        unknown = true;
+                        "unknown".to_string()
+                    }
+                };
+                let style = match edge.kind {
+                    EdgeKind::Next => "",
+                    EdgeKind::Taken => " [label=taken]",
+                    EdgeKind::NotTaken => " [label=\"not taken\", style=dashed]",
+                };
+                writeln!(dot, "    b{} -> {}{};", start, to, style).unwrap();
+            }
+        }
+
+        for address in invalid {
+            writeln!(
+                dot,
+                "    invalid{} [label=\"{}: not an instruction\", shape=plaintext];",
+                address, address
+            )
+            .unwrap();
+        }
+        if unknown {
+            dot.push_str("    unknown [label=\"?\", shape=circle];\n");
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::five;
+    use crate::intcode::fixtures::COMPARE;
+    use crate::intcode::Machine;
+
+    fn starts(cfg: &Cfg) -> Vec<usize> {
+        cfg.blocks.keys().cloned().collect()
+    }
+
+    #[test]
+    fn blocks_and_edges() {
+        let cfg = Cfg::build(&COMPARE, 0);
+
+        assert_eq!(starts(&cfg), vec![0, 9, 16, 22, 31, 36, 46]);
+        assert_eq!(cfg.blocks[&0].instructions.len(), 3);
+        assert_eq!(
+            cfg.blocks[&0].edges,
+            vec![
+                Edge {
+                    kind: EdgeKind::Taken,
+                    to: Target::Block(22)
+                },
+                Edge {
+                    kind: EdgeKind::NotTaken,
+                    to: Target::Block(9)
+                },
+            ]
+        );
+        // JF #0 always jumps
+        assert_eq!(
+            cfg.blocks[&16].edges,
+            vec![Edge {
+                kind: EdgeKind::Taken,
+                to: Target::Block(36)
+            }]
+        );
+        assert!(cfg.blocks[&46].edges.is_empty());
+    }
+
+    #[test]
+    fn indirect_and_invalid() {
+        // JT [7] [8], then a jump to the word 98
+        let program = vec![5, 7, 8, 1105, 1, 9, 99, 1, 6, 98];
+        let cfg = Cfg::build(&program, 0);
+
+        assert_eq!(starts(&cfg), vec![0, 3]);
+        assert_eq!(cfg.blocks[&0].edges[0].to, Target::Unknown);
+        assert_eq!(cfg.blocks[&3].edges[0].to, Target::Invalid(9));
+
+        assert_eq!(
+            cfg.to_dot(),
+            concat!(
+                "digraph intcode {\n",
+                "    node [shape=box, fontname=monospace];\n",
+                "    b0 [label=\"0: JT [7] [8]\\l\"];\n",
+                "    b0 -> unknown [label=taken];\n",
+                "    b0 -> b3 [label=\"not taken\", style=dashed];\n",
+                "    b3 [label=\"3: JT #1 #9\\l\"];\n",
+                "    b3 -> invalid9 [label=taken];\n",
+                "    invalid9 [label=\"9: not an instruction\", shape=plaintext];\n",
+                "    unknown [label=\"?\", shape=circle];\n",
+                "}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn diagnostic() {
+        let program = five::load_input();
+
+        // the diagnostic patches in the instruction at 6 as it runs
+        let cfg = Cfg::build(&program, 0);
+        assert_eq!(starts(&cfg), vec![0]);
+        assert_eq!(cfg.blocks[&0].edges[0].to, Target::Invalid(6));
+
+        // so the graph worth drawing is of memory once it has
+        let mut machine = Machine::new(&program);
+        machine.provide_input(5);
+        machine.step().unwrap();
+        machine.step().unwrap();
+        let cfg = Cfg::build(machine.memory(), machine.pc());
+
+        // it tests each opcode in turn, up to where it calls a subroutine
+        // through an address stored in memory
+        assert_eq!(cfg.blocks.len(), 13);
+        assert_eq!(
+            cfg.blocks[&280].edges,
+            vec![Edge {
+                kind: EdgeKind::Taken,
+                to: Target::Unknown
+            }]
+        );
+        let dot = cfg.to_dot();
+        assert!(dot.contains("    b6 [label=\"6: JT #1 #238\\l\"];\n"));
+        assert!(dot.contains("    b280 -> unknown [label=taken];\n"));
+    }
+}
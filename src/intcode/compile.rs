@@ -0,0 +1,384 @@
+// Translates an Intcode program into a Rust function that does the same as
+// running it in a fresh machine, for programs that get run a great many
+// times. Each instruction reachable from the start becomes an arm of a match
+// on pc with its parameters baked in, which is only right as long as nothing
+// writes over them:
+//
+// - an instruction that writes to a fixed address inside the compiled code
+//   is self modifying, and is left out when translating
+// - a relative mode write is checked against the compiled code as it runs
+//
+// Whenever the compiled code reaches something it can't handle, a left out
+// instruction, a jump somewhere that wasn't compiled, or anything that would
+// be an error, it hands its state over to the interpreter and lets that
+// carry on from the same instruction. So the function always gives the same
+// answer the interpreter would, errors included.
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+
+use super::cfg::{reachable, Instruction};
+use super::{ArgMode, IntcodeError, Machine, Memory, OpCode};
+
+// The imports a file of compiled functions needs.
+pub const PRELUDE: &str = "\
+use std::convert::TryFrom;
+
+use crate::intcode::compile::State;
+use crate::intcode::IntcodeError;
+";
+
+// What compiled code runs on, and hands to the interpreter when it gives up.
+pub struct State {
+    pub memory: Memory,
+    pub pc: usize,
+    pub relative_base: i64,
+    pub input: VecDeque<i64>,
+    pub output: Vec<i64>,
+    pub executed: u64,
+}
+
+impl State {
+    pub fn new(program: &[i64], input: &[i64]) -> State {
+        State {
+            memory: Memory::new(program),
+            pc: 0,
+            relative_base: 0,
+            input: input.iter().cloned().collect(),
+            output: vec![],
+            executed: 0,
+        }
+    }
+
+    // The address offset from the relative base, if it is a valid one.
+    pub fn relative(&self, offset: i64) -> Option<usize> {
+        let address = self.relative_base.checked_add(offset)?;
+        if address < 0 {
+            None
+        } else {
+            Some(address as usize)
+        }
+    }
+
+    // The final memory and everything output, once the program halts.
+    pub fn finish(self) -> (Vec<i64>, Vec<i64>) {
+        (self.memory.into_vec(), self.output)
+    }
+
+    // Runs the rest of the program in the interpreter, starting with the
+    // instruction at pc.
+    pub fn interpret(self, budget: Option<u64>) -> Result<(Vec<i64>, Vec<i64>), IntcodeError> {
+        let mut machine = Machine::new(&[]);
+        machine.memory = self.memory;
+        machine.pc = self.pc;
+        machine.relative_base = self.relative_base;
+        machine.input = self.input;
+        machine.output = self.output;
+        machine.executed = self.executed;
+        machine.set_budget(budget);
+
+        machine.run()?;
+
+        Ok((machine.memory.into_vec(), machine.output))
+    }
+}
+
+// The compiled code as address ranges, merging any that touch or overlap.
+fn code_ranges(instructions: &BTreeMap<usize, Instruction>) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for (address, instruction) in instructions {
+        match ranges.last_mut() {
+            Some((_, end)) if *address <= *end => *end = (*end).max(instruction.next),
+            _ => ranges.push((*address, instruction.next)),
+        }
+    }
+
+    ranges
+}
+
+const GIVE_UP: &str = "return state.interpret(budget)";
+
+// A statement binding var to the value of a read parameter.
+fn read_param(var: &str, mode: ArgMode, param: i64) -> Option<String> {
+    match mode {
+        ArgMode::Position if param < 0 => None,
+        ArgMode::Position => Some(format!("let {} = state.memory.read({});", var, param)),
+        ArgMode::Immediate => Some(format!("let {}: i64 = {};", var, param)),
+        ArgMode::Relative => Some(format!(
+            "let {} = match state.relative({}) {{ Some(address) => state.memory.read(address), None => {} }};",
+            var, param, GIVE_UP
+        )),
+    }
+}
+
+// A statement binding dest to the address a written parameter names, and
+// whether that address is known not to be compiled code.
+fn write_param(mode: ArgMode, param: i64, in_code: &dyn Fn(usize) -> bool) -> Option<String> {
+    match mode {
+        ArgMode::Position if param < 0 || in_code(param as usize) => None,
+        ArgMode::Position => Some(format!("let dest = {};", param)),
+        ArgMode::Immediate => None,
+        ArgMode::Relative => Some(format!(
+            "let dest = match state.relative({}) {{ Some(address) if !is_code(address) => address, _ => {} }};",
+            param, GIVE_UP
+        )),
+    }
+}
+
+// Where a jump goes when it is taken.
+fn jump_target(mode: ArgMode, param: i64) -> Option<String> {
+    match mode {
+        ArgMode::Immediate if param < 0 => Some(format!("{};", GIVE_UP)),
+        ArgMode::Immediate => Some(format!("state.pc = {};", param)),
+        _ => Some(format!(
+            "{}\n                    state.pc = match usize::try_from(b) {{ Ok(target) => target, Err(_) => {} }};",
+            read_param("b", mode, param)?,
+            GIVE_UP
+        )),
+    }
+}
+
+// The body of an instruction's match arm, or None if it is left to the
+// interpreter.
+fn translate(instruction: &Instruction, in_code: &dyn Fn(usize) -> bool) -> Option<Vec<String>> {
+    let modes = instruction.modes;
+    let params = &instruction.params;
+    let next = format!("state.pc = {};", instruction.next);
+
+    let lines = match instruction.opcode {
+        OpCode::Add | OpCode::Mult | OpCode::LessThan | OpCode::Equals => {
+            let result = match instruction.opcode {
+                OpCode::Add => format!("let value = match a.checked_add(b) {{ Some(value) => value, None => {} }};", GIVE_UP),
+                OpCode::Mult => format!("let value = match a.checked_mul(b) {{ Some(value) => value, None => {} }};", GIVE_UP),
+                OpCode::LessThan => "let value = i64::from(a < b);".to_string(),
+                _ => "let value = i64::from(a == b);".to_string(),
+            };
+            vec![
+                read_param("a", modes[0], params[0])?,
+                read_param("b", modes[1], params[1])?,
+                write_param(modes[2], params[2], in_code)?,
+                result,
+                "state.memory.write(dest, value);".to_string(),
+                next,
+            ]
+        }
+        OpCode::Input => vec![
+            write_param(modes[0], params[0], in_code)?,
+            format!("let value = match state.input.pop_front() {{ Some(value) => value, None => {} }};", GIVE_UP),
+            "state.memory.write(dest, value);".to_string(),
+            next,
+        ],
+        OpCode::Output => vec![
+            read_param("a", modes[0], params[0])?,
+            "state.output.push(a);".to_string(),
+            next,
+        ],
+        OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+            let test = if instruction.opcode == OpCode::JumpIfTrue { "!=" } else { "==" };
+            vec![
+                read_param("a", modes[0], params[0])?,
+                format!("if a {} 0 {{", test),
+                format!("    {}", jump_target(modes[1], params[1])?),
+                "} else {".to_string(),
+                format!("    {}", next),
+                "}".to_string(),
+            ]
+        }
+        OpCode::AdjustRelativeBase => vec![
+            read_param("a", modes[0], params[0])?,
+            format!("state.relative_base = match state.relative_base.checked_add(a) {{ Some(base) => base, None => {} }};", GIVE_UP),
+            next,
+        ],
+        OpCode::Halt => vec!["return Ok(state.finish());".to_string()],
+    };
+
+    Some(lines)
+}
+
+// The source of a function called name that takes the program's input and
+// returns its final memory and output, like running it in a machine with
+// the given instruction budget.
+pub fn compile(name: &str, program: &[i64], budget: Option<u64>) -> String {
+    let instructions = reachable(program, 0);
+    let ranges = code_ranges(&instructions);
+    let in_code = |address: usize| {
+        ranges
+            .iter()
+            .any(|&(start, end)| start <= address && address < end)
+    };
+
+    let mut arms = String::new();
+    let mut checks_code = false;
+    for (address, instruction) in &instructions {
+        match translate(instruction, &in_code) {
+            Some(lines) => {
+                checks_code |= lines.iter().any(|line| line.contains("is_code"));
+                writeln!(arms, "            // {}: {}", address, instruction.text).unwrap();
+                writeln!(arms, "            {} => {{", address).unwrap();
+                for line in lines {
+                    writeln!(arms, "                {}", line).unwrap();
+                }
+                writeln!(arms, "            }}").unwrap();
+            }
+            None => {
+                writeln!(
+                    arms,
+                    "            // {}: {} is left to the interpreter",
+                    address, instruction.text
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    let mut source = String::new();
+    writeln!(
+        source,
+        "// Compiled from a {} word Intcode program.",
+        program.len()
+    )
+    .unwrap();
+    writeln!(
+        source,
+        "pub fn {}(input: &[i64]) -> Result<(Vec<i64>, Vec<i64>), IntcodeError> {{",
+        name
+    )
+    .unwrap();
+    writeln!(source, "    const PROGRAM: [i64; {}] = [", program.len()).unwrap();
+    for chunk in program.chunks(16) {
+        let words: Vec<String> = chunk.iter().map(|word| word.to_string()).collect();
+        writeln!(source, "        {},", words.join(", ")).unwrap();
+    }
+    writeln!(source, "    ];").unwrap();
+    if checks_code {
+        let ranges: Vec<String> = ranges
+            .iter()
+            .map(|(start, end)| format!("({}, {})", start, end))
+            .collect();
+        writeln!(
+            source,
+            "    const CODE: [(usize, usize); {}] = [{}];",
+            ranges.len(),
+            ranges.join(", ")
+        )
+        .unwrap();
+        writeln!(source, "    let is_code = |address: usize| CODE.iter().any(|&(start, end)| start <= address && address < end);").unwrap();
+    }
+    writeln!(source, "    let budget: Option<u64> = {:?};", budget).unwrap();
+    writeln!(source, "    let mut state = State::new(&PROGRAM, input);").unwrap();
+    writeln!(source, "    loop {{").unwrap();
+    writeln!(source, "        if let Some(budget) = budget {{").unwrap();
+    writeln!(source, "            if state.executed >= budget {{").unwrap();
+    writeln!(
+        source,
+        "                return state.interpret(Some(budget));"
+    )
+    .unwrap();
+    writeln!(source, "            }}").unwrap();
+    writeln!(source, "        }}").unwrap();
+    writeln!(source, "        match state.pc {{").unwrap();
+    source.push_str(&arms);
+    writeln!(source, "            _ => {},", GIVE_UP).unwrap();
+    writeln!(source, "        }}").unwrap();
+    writeln!(source, "        state.executed += 1;").unwrap();
+    writeln!(source, "    }}").unwrap();
+    writeln!(source, "}}").unwrap();
+
+    source
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod generated;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::five;
+    use crate::intcode::fixtures::COMPARE;
+    use std::fs;
+
+    // Everything compiled into generated.rs.
+    fn cases() -> Vec<(&'static str, Vec<i64>)> {
+        vec![
+            ("equals_eight", vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]),
+            // writes over a parameter of the instruction at 2
+            ("less_than_eight", vec![3, 3, 1107, -1, 8, 3, 4, 3, 99]),
+            (
+                "is_zero",
+                vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9],
+            ),
+            ("compare_to_eight", COMPARE.to_vec()),
+            (
+                "quine",
+                vec![
+                    109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+                ],
+            ),
+            ("spin", vec![1105, 1, 0]),
+            ("diagnostic", five::load_input()),
+        ]
+    }
+
+    fn generated_source() -> String {
+        let mut source = String::from("// Generated by intcode::compile::tests::regenerate.\n");
+        source.push_str(PRELUDE);
+        for (name, program) in cases() {
+            source.push('\n');
+            source.push_str(&compile(name, &program, Some(five::BUDGET)));
+        }
+
+        source
+    }
+
+    const GENERATED: &str = "src/intcode/compile/generated.rs";
+
+    // cargo test regenerate -- --ignored
+    #[test]
+    #[ignore]
+    fn regenerate() {
+        fs::write(GENERATED, generated_source()).unwrap();
+    }
+
+    #[test]
+    fn generated_is_current() {
+        assert!(
+            fs::read_to_string(GENERATED).unwrap() == generated_source(),
+            "{} is out of date, regenerate it",
+            GENERATED
+        );
+    }
+
+    #[test]
+    fn self_modifying_left_out() {
+        let source = compile("less_than_eight", &cases()[1].1, None);
+        assert!(source.contains("// 0: IN [3] is left to the interpreter\n"));
+        assert!(!source.contains("            0 => {"));
+        assert!(source.contains("            6 => {"));
+    }
+
+    #[test]
+    fn matches_interpreter() {
+        type Compiled = fn(&[i64]) -> Result<(Vec<i64>, Vec<i64>), IntcodeError>;
+        let compiled: Vec<Compiled> = vec![
+            generated::equals_eight,
+            generated::less_than_eight,
+            generated::is_zero,
+            generated::compare_to_eight,
+            generated::quine,
+            generated::spin,
+            generated::diagnostic,
+        ];
+
+        for ((name, program), compiled) in cases().into_iter().zip(compiled) {
+            for &input in &[-1, 0, 7, 8, 9, 12] {
+                assert_eq!(
+                    compiled(&[input]),
+                    five::run_program(program.clone(), input),
+                    "{} with input {}",
+                    name,
+                    input
+                );
+            }
+        }
+    }
+}
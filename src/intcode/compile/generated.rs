@@ -0,0 +1,450 @@
+// Generated by intcode::compile::tests::regenerate.
+use std::convert::TryFrom;
+
+use crate::intcode::compile::State;
+use crate::intcode::IntcodeError;
+
+// Compiled from a 11 word Intcode program.
+pub fn equals_eight(input: &[i64]) -> Result<(Vec<i64>, Vec<i64>), IntcodeError> {
+    const PROGRAM: [i64; 11] = [
+        3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8,
+    ];
+    let budget: Option<u64> = Some(1000000);
+    let mut state = State::new(&PROGRAM, input);
+    loop {
+        if let Some(budget) = budget {
+            if state.executed >= budget {
+                return state.interpret(Some(budget));
+            }
+        }
+        match state.pc {
+            // 0: IN [9]
+            0 => {
+                let dest = 9;
+                let value = match state.input.pop_front() { Some(value) => value, None => return state.interpret(budget) };
+                state.memory.write(dest, value);
+                state.pc = 2;
+            }
+            // 2: EQ [9] [10] [9]
+            2 => {
+                let a = state.memory.read(9);
+                let b = state.memory.read(10);
+                let dest = 9;
+                let value = i64::from(a == b);
+                state.memory.write(dest, value);
+                state.pc = 6;
+            }
+            // 6: OUT [9]
+            6 => {
+                let a = state.memory.read(9);
+                state.output.push(a);
+                state.pc = 8;
+            }
+            // 8: HLT
+            8 => {
+                return Ok(state.finish());
+            }
+            _ => return state.interpret(budget),
+        }
+        state.executed += 1;
+    }
+}
+
+// Compiled from a 9 word Intcode program.
+pub fn less_than_eight(input: &[i64]) -> Result<(Vec<i64>, Vec<i64>), IntcodeError> {
+    const PROGRAM: [i64; 9] = [
+        3, 3, 1107, -1, 8, 3, 4, 3, 99,
+    ];
+    let budget: Option<u64> = Some(1000000);
+    let mut state = State::new(&PROGRAM, input);
+    loop {
+        if let Some(budget) = budget {
+            if state.executed >= budget {
+                return state.interpret(Some(budget));
+            }
+        }
+        match state.pc {
+            // 0: IN [3] is left to the interpreter
+            // 2: LT #-1 #8 [3] is left to the interpreter
+            // 6: OUT [3]
+            6 => {
+                let a = state.memory.read(3);
+                state.output.push(a);
+                state.pc = 8;
+            }
+            // 8: HLT
+            8 => {
+                return Ok(state.finish());
+            }
+            _ => return state.interpret(budget),
+        }
+        state.executed += 1;
+    }
+}
+
+// Compiled from a 16 word Intcode program.
+pub fn is_zero(input: &[i64]) -> Result<(Vec<i64>, Vec<i64>), IntcodeError> {
+    const PROGRAM: [i64; 16] = [
+        3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9,
+    ];
+    let budget: Option<u64> = Some(1000000);
+    let mut state = State::new(&PROGRAM, input);
+    loop {
+        if let Some(budget) = budget {
+            if state.executed >= budget {
+                return state.interpret(Some(budget));
+            }
+        }
+        match state.pc {
+            // 0: IN [12]
+            0 => {
+                let dest = 12;
+                let value = match state.input.pop_front() { Some(value) => value, None => return state.interpret(budget) };
+                state.memory.write(dest, value);
+                state.pc = 2;
+            }
+            // 2: JF [12] [15]
+            2 => {
+                let a = state.memory.read(12);
+                if a == 0 {
+                    let b = state.memory.read(15);
+                    state.pc = match usize::try_from(b) { Ok(target) => target, Err(_) => return state.interpret(budget) };
+                } else {
+                    state.pc = 5;
+                }
+            }
+            // 5: ADD [13] [14] [13]
+            5 => {
+                let a = state.memory.read(13);
+                let b = state.memory.read(14);
+                let dest = 13;
+                let value = match a.checked_add(b) { Some(value) => value, None => return state.interpret(budget) };
+                state.memory.write(dest, value);
+                state.pc = 9;
+            }
+            // 9: OUT [13]
+            9 => {
+                let a = state.memory.read(13);
+                state.output.push(a);
+                state.pc = 11;
+            }
+            // 11: HLT
+            11 => {
+                return Ok(state.finish());
+            }
+            _ => return state.interpret(budget),
+        }
+        state.executed += 1;
+    }
+}
+
+// Compiled from a 47 word Intcode program.
+pub fn compare_to_eight(input: &[i64]) -> Result<(Vec<i64>, Vec<i64>), IntcodeError> {
+    const PROGRAM: [i64; 47] = [
+        3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31,
+        1106, 0, 36, 98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104,
+        999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105, 1, 46, 98, 99,
+    ];
+    let budget: Option<u64> = Some(1000000);
+    let mut state = State::new(&PROGRAM, input);
+    loop {
+        if let Some(budget) = budget {
+            if state.executed >= budget {
+                return state.interpret(Some(budget));
+            }
+        }
+        match state.pc {
+            // 0: IN [21]
+            0 => {
+                let dest = 21;
+                let value = match state.input.pop_front() { Some(value) => value, None => return state.interpret(budget) };
+                state.memory.write(dest, value);
+                state.pc = 2;
+            }
+            // 2: EQ [21] #8 [20]
+            2 => {
+                let a = state.memory.read(21);
+                let b: i64 = 8;
+                let dest = 20;
+                let value = i64::from(a == b);
+                state.memory.write(dest, value);
+                state.pc = 6;
+            }
+            // 6: JT [20] #22
+            6 => {
+                let a = state.memory.read(20);
+                if a != 0 {
+                    state.pc = 22;
+                } else {
+                    state.pc = 9;
+                }
+            }
+            // 9: LT #8 [21] [20]
+            9 => {
+                let a: i64 = 8;
+                let b = state.memory.read(21);
+                let dest = 20;
+                let value = i64::from(a < b);
+                state.memory.write(dest, value);
+                state.pc = 13;
+            }
+            // 13: JF [20] #31
+            13 => {
+                let a = state.memory.read(20);
+                if a == 0 {
+                    state.pc = 31;
+                } else {
+                    state.pc = 16;
+                }
+            }
+            // 16: JF #0 #36
+            16 => {
+                let a: i64 = 0;
+                if a == 0 {
+                    state.pc = 36;
+                } else {
+                    state.pc = 19;
+                }
+            }
+            // 22: MUL [21] #125 [20]
+            22 => {
+                let a = state.memory.read(21);
+                let b: i64 = 125;
+                let dest = 20;
+                let value = match a.checked_mul(b) { Some(value) => value, None => return state.interpret(budget) };
+                state.memory.write(dest, value);
+                state.pc = 26;
+            }
+            // 26: OUT [20]
+            26 => {
+                let a = state.memory.read(20);
+                state.output.push(a);
+                state.pc = 28;
+            }
+            // 28: JT #1 #46
+            28 => {
+                let a: i64 = 1;
+                if a != 0 {
+                    state.pc = 46;
+                } else {
+                    state.pc = 31;
+                }
+            }
+            // 31: OUT #999
+            31 => {
+                let a: i64 = 999;
+                state.output.push(a);
+                state.pc = 33;
+            }
+            // 33: JT #1 #46
+            33 => {
+                let a: i64 = 1;
+                if a != 0 {
+                    state.pc = 46;
+                } else {
+                    state.pc = 36;
+                }
+            }
+            // 36: ADD #1000 #1 [20]
+            36 => {
+                let a: i64 = 1000;
+                let b: i64 = 1;
+                let dest = 20;
+                let value = match a.checked_add(b) { Some(value) => value, None => return state.interpret(budget) };
+                state.memory.write(dest, value);
+                state.pc = 40;
+            }
+            // 40: OUT [20]
+            40 => {
+                let a = state.memory.read(20);
+                state.output.push(a);
+                state.pc = 42;
+            }
+            // 42: JT #1 #46
+            42 => {
+                let a: i64 = 1;
+                if a != 0 {
+                    state.pc = 46;
+                } else {
+                    state.pc = 45;
+                }
+            }
+            // 46: HLT
+            46 => {
+                return Ok(state.finish());
+            }
+            _ => return state.interpret(budget),
+        }
+        state.executed += 1;
+    }
+}
+
+// Compiled from a 16 word Intcode program.
+pub fn quine(input: &[i64]) -> Result<(Vec<i64>, Vec<i64>), IntcodeError> {
+    const PROGRAM: [i64; 16] = [
+        109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+    ];
+    let budget: Option<u64> = Some(1000000);
+    let mut state = State::new(&PROGRAM, input);
+    loop {
+        if let Some(budget) = budget {
+            if state.executed >= budget {
+                return state.interpret(Some(budget));
+            }
+        }
+        match state.pc {
+            // 0: ARB #1
+            0 => {
+                let a: i64 = 1;
+                state.relative_base = match state.relative_base.checked_add(a) { Some(base) => base, None => return state.interpret(budget) };
+                state.pc = 2;
+            }
+            // 2: OUT [rb-1]
+            2 => {
+                let a = match state.relative(-1) { Some(address) => state.memory.read(address), None => return state.interpret(budget) };
+                state.output.push(a);
+                state.pc = 4;
+            }
+            // 4: ADD [100] #1 [100]
+            4 => {
+                let a = state.memory.read(100);
+                let b: i64 = 1;
+                let dest = 100;
+                let value = match a.checked_add(b) { Some(value) => value, None => return state.interpret(budget) };
+                state.memory.write(dest, value);
+                state.pc = 8;
+            }
+            // 8: EQ [100] #16 [101]
+            8 => {
+                let a = state.memory.read(100);
+                let b: i64 = 16;
+                let dest = 101;
+                let value = i64::from(a == b);
+                state.memory.write(dest, value);
+                state.pc = 12;
+            }
+            // 12: JF [101] #0
+            12 => {
+                let a = state.memory.read(101);
+                if a == 0 {
+                    state.pc = 0;
+                } else {
+                    state.pc = 15;
+                }
+            }
+            // 15: HLT
+            15 => {
+                return Ok(state.finish());
+            }
+            _ => return state.interpret(budget),
+        }
+        state.executed += 1;
+    }
+}
+
+// Compiled from a 3 word Intcode program.
+pub fn spin(input: &[i64]) -> Result<(Vec<i64>, Vec<i64>), IntcodeError> {
+    const PROGRAM: [i64; 3] = [
+        1105, 1, 0,
+    ];
+    let budget: Option<u64> = Some(1000000);
+    let mut state = State::new(&PROGRAM, input);
+    loop {
+        if let Some(budget) = budget {
+            if state.executed >= budget {
+                return state.interpret(Some(budget));
+            }
+        }
+        match state.pc {
+            // 0: JT #1 #0
+            0 => {
+                let a: i64 = 1;
+                if a != 0 {
+                    state.pc = 0;
+                } else {
+                    state.pc = 3;
+                }
+            }
+            _ => return state.interpret(budget),
+        }
+        state.executed += 1;
+    }
+}
+
+// Compiled from a 678 word Intcode program.
+pub fn diagnostic(input: &[i64]) -> Result<(Vec<i64>, Vec<i64>), IntcodeError> {
+    const PROGRAM: [i64; 678] = [
+        3, 225, 1, 225, 6, 6, 1100, 1, 238, 225, 104, 0, 1101, 9, 90, 224,
+        1001, 224, -99, 224, 4, 224, 102, 8, 223, 223, 1001, 224, 6, 224, 1, 223,
+        224, 223, 1102, 26, 62, 225, 1101, 11, 75, 225, 1101, 90, 43, 225, 2, 70,
+        35, 224, 101, -1716, 224, 224, 4, 224, 1002, 223, 8, 223, 101, 4, 224, 224,
+        1, 223, 224, 223, 1101, 94, 66, 225, 1102, 65, 89, 225, 101, 53, 144, 224,
+        101, -134, 224, 224, 4, 224, 1002, 223, 8, 223, 1001, 224, 5, 224, 1, 224,
+        223, 223, 1102, 16, 32, 224, 101, -512, 224, 224, 4, 224, 102, 8, 223, 223,
+        101, 5, 224, 224, 1, 224, 223, 223, 1001, 43, 57, 224, 101, -147, 224, 224,
+        4, 224, 102, 8, 223, 223, 101, 4, 224, 224, 1, 223, 224, 223, 1101, 36,
+        81, 225, 1002, 39, 9, 224, 1001, 224, -99, 224, 4, 224, 1002, 223, 8, 223,
+        101, 2, 224, 224, 1, 223, 224, 223, 1, 213, 218, 224, 1001, 224, -98, 224,
+        4, 224, 102, 8, 223, 223, 101, 2, 224, 224, 1, 224, 223, 223, 102, 21,
+        74, 224, 101, -1869, 224, 224, 4, 224, 102, 8, 223, 223, 1001, 224, 7, 224,
+        1, 224, 223, 223, 1101, 25, 15, 225, 1101, 64, 73, 225, 4, 223, 99, 0,
+        0, 0, 677, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1105, 0,
+        99999, 1105, 227, 247, 1105, 1, 99999, 1005, 227, 99999, 1005, 0, 256, 1105, 1, 99999,
+        1106, 227, 99999, 1106, 0, 265, 1105, 1, 99999, 1006, 0, 99999, 1006, 227, 274, 1105,
+        1, 99999, 1105, 1, 280, 1105, 1, 99999, 1, 225, 225, 225, 1101, 294, 0, 0,
+        105, 1, 0, 1105, 1, 99999, 1106, 0, 300, 1105, 1, 99999, 1, 225, 225, 225,
+        1101, 314, 0, 0, 106, 0, 0, 1105, 1, 99999, 1008, 226, 677, 224, 1002, 223,
+        2, 223, 1005, 224, 329, 1001, 223, 1, 223, 1007, 677, 677, 224, 102, 2, 223,
+        223, 1005, 224, 344, 101, 1, 223, 223, 108, 226, 677, 224, 102, 2, 223, 223,
+        1006, 224, 359, 101, 1, 223, 223, 108, 226, 226, 224, 1002, 223, 2, 223, 1005,
+        224, 374, 1001, 223, 1, 223, 7, 226, 226, 224, 1002, 223, 2, 223, 1006, 224,
+        389, 1001, 223, 1, 223, 8, 226, 677, 224, 1002, 223, 2, 223, 1006, 224, 404,
+        1001, 223, 1, 223, 107, 677, 677, 224, 1002, 223, 2, 223, 1006, 224, 419, 101,
+        1, 223, 223, 1008, 677, 677, 224, 102, 2, 223, 223, 1006, 224, 434, 101, 1,
+        223, 223, 1107, 226, 677, 224, 102, 2, 223, 223, 1005, 224, 449, 1001, 223, 1,
+        223, 107, 226, 226, 224, 102, 2, 223, 223, 1006, 224, 464, 101, 1, 223, 223,
+        107, 226, 677, 224, 102, 2, 223, 223, 1005, 224, 479, 1001, 223, 1, 223, 8,
+        677, 226, 224, 102, 2, 223, 223, 1005, 224, 494, 1001, 223, 1, 223, 1108, 226,
+        677, 224, 102, 2, 223, 223, 1006, 224, 509, 101, 1, 223, 223, 1107, 677, 226,
+        224, 1002, 223, 2, 223, 1005, 224, 524, 101, 1, 223, 223, 1008, 226, 226, 224,
+        1002, 223, 2, 223, 1005, 224, 539, 101, 1, 223, 223, 7, 226, 677, 224, 1002,
+        223, 2, 223, 1005, 224, 554, 101, 1, 223, 223, 1107, 677, 677, 224, 1002, 223,
+        2, 223, 1006, 224, 569, 1001, 223, 1, 223, 8, 226, 226, 224, 1002, 223, 2,
+        223, 1006, 224, 584, 101, 1, 223, 223, 1108, 677, 677, 224, 102, 2, 223, 223,
+        1005, 224, 599, 101, 1, 223, 223, 108, 677, 677, 224, 1002, 223, 2, 223, 1006,
+        224, 614, 101, 1, 223, 223, 1007, 226, 226, 224, 102, 2, 223, 223, 1005, 224,
+        629, 1001, 223, 1, 223, 7, 677, 226, 224, 1002, 223, 2, 223, 1005, 224, 644,
+        101, 1, 223, 223, 1007, 226, 677, 224, 102, 2, 223, 223, 1005, 224, 659, 1001,
+        223, 1, 223, 1108, 677, 226, 224, 102, 2, 223, 223, 1006, 224, 674, 101, 1,
+        223, 223, 4, 223, 99, 226,
+    ];
+    let budget: Option<u64> = Some(1000000);
+    let mut state = State::new(&PROGRAM, input);
+    loop {
+        if let Some(budget) = budget {
+            if state.executed >= budget {
+                return state.interpret(Some(budget));
+            }
+        }
+        match state.pc {
+            // 0: IN [225]
+            0 => {
+                let dest = 225;
+                let value = match state.input.pop_front() { Some(value) => value, None => return state.interpret(budget) };
+                state.memory.write(dest, value);
+                state.pc = 2;
+            }
+            // 2: ADD [225] [6] [6]
+            2 => {
+                let a = state.memory.read(225);
+                let b = state.memory.read(6);
+                let dest = 6;
+                let value = match a.checked_add(b) { Some(value) => value, None => return state.interpret(budget) };
+                state.memory.write(dest, value);
+                state.pc = 6;
+            }
+            _ => return state.interpret(budget),
+        }
+        state.executed += 1;
+    }
+}
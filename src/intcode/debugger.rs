@@ -0,0 +1,431 @@
+// A line-oriented debugger. Each line of input is a command:
+//
+//   s, step [n]        run n instructions (default 1)
+//   c, continue        run to the next output, breakpoint or watchpoint
+//   b, break <addr>    toggle a breakpoint on an instruction address
+//   w, watch <addr>    toggle a watchpoint on a memory cell
+//   r, regs            print pc and the relative base
+//   m, mem <addr> [n]  print n memory cells (default 8)
+//   l, list [n]        disassemble n instructions from pc (default 5)
+//   i, input <value>   queue a value for the program's next Input
+//   sb, back [n]       step back n instructions (default 1)
+//   bw <addr>          run back to before the last write to a memory cell
+//   bo                 run back to before the last output
+//   q, quit
+//
+// The machine keeps an undo log while it is being debugged, so stepping
+// back doesn't need to run anything again.
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+use super::disasm::decode;
+use super::{Machine, Status};
+
+// The most a count can ask for, more would print or step for ages.
+const MAX_COUNT: i64 = 100_000;
+
+pub struct Debugger {
+    machine: Machine,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<usize>,
+    halted: bool,
+}
+
+// Why a step, or a run of steps, stopped.
+enum Stop {
+    Output(i64),
+    NeedsInput,
+    Halted,
+    Breakpoint(usize),
+    Watchpoint { address: usize, old: i64, new: i64 },
+    Error(String),
+}
+
+impl Debugger {
+    pub fn new(mut machine: Machine) -> Debugger {
+        machine.record_history();
+
+        Debugger {
+            machine,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            halted: false,
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn into_machine(self) -> Machine {
+        self.machine
+    }
+
+    // Reads commands until quit or the end of input.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut out: W) -> io::Result<()> {
+        self.print_next(&mut out)?;
+        write!(out, "> ")?;
+        out.flush()?;
+
+        for line in input.lines() {
+            if !self.command(&line?, &mut out)? {
+                break;
+            }
+            write!(out, "> ")?;
+            out.flush()?;
+        }
+
+        Ok(())
+    }
+
+    // Runs one command, returns false when it's time to quit.
+    pub fn command<W: Write>(&mut self, line: &str, out: &mut W) -> io::Result<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let command = match words.first() {
+            Some(command) => *command,
+            None => return Ok(true),
+        };
+        let args: Vec<i64> = match words[1..].iter().map(|word| word.parse()).collect() {
+            Ok(args) => args,
+            Err(_) => {
+                writeln!(out, "arguments must be numbers")?;
+                return Ok(true);
+            }
+        };
+        // input takes a value, everything else an address or a count
+        if command != "i" && command != "input" && args.iter().any(|arg| *arg < 0) {
+            writeln!(out, "addresses and counts can't be negative")?;
+            return Ok(true);
+        }
+        let count = match command {
+            "s" | "step" | "l" | "list" | "sb" | "back" => args.first(),
+            "m" | "mem" => args.get(1),
+            _ => None,
+        };
+        if count.is_some_and(|count| *count > MAX_COUNT) {
+            writeln!(out, "counts can't be more than {}", MAX_COUNT)?;
+            return Ok(true);
+        }
+        let arg = |i: usize, default: i64| args.get(i).cloned().unwrap_or(default);
+        let address = |i: usize| args.get(i).map(|a| *a as usize);
+
+        match command {
+            "s" | "step" => {
+                for _ in 0..arg(0, 1) {
+                    if let Some(stop) = self.step() {
+                        self.print_stop(&stop, out)?;
+                        break;
+                    }
+                }
+                self.print_next(out)?;
+            }
+            "c" | "continue" => {
+                let stop = self.continue_run();
+                self.print_stop(&stop, out)?;
+                self.print_next(out)?;
+            }
+            "b" | "break" => match address(0) {
+                Some(address) => toggle(&mut self.breakpoints, address, "breakpoint", out)?,
+                None => writeln!(out, "break needs an address")?,
+            },
+            "w" | "watch" => match address(0) {
+                Some(address) => toggle(&mut self.watchpoints, address, "watchpoint", out)?,
+                None => writeln!(out, "watch needs an address")?,
+            },
+            "r" | "regs" => writeln!(
+                out,
+                "pc: {} rb: {}",
+                self.machine.pc(),
+                self.machine.relative_base()
+            )?,
+            "m" | "mem" => match address(0) {
+                Some(start) => {
+                    let count = arg(1, 8) as usize;
+                    let values: Vec<String> = (start..start + count)
+                        .map(|address| self.machine.read(address).to_string())
+                        .collect();
+                    writeln!(out, "{:>5}: {}", start, values.join(" "))?;
+                }
+                None => writeln!(out, "mem needs an address")?,
+            },
+            "l" | "list" => {
+                let mut address = self.machine.pc();
+                for _ in 0..arg(0, 5) {
+                    address += self.print_at(address, out)?;
+                }
+            }
+            "i" | "input" => match args.first() {
+                Some(value) => self.machine.provide_input(*value),
+                None => writeln!(out, "input needs a value")?,
+            },
+            "sb" | "back" => {
+                for _ in 0..arg(0, 1) {
+                    if !self.back() {
+                        writeln!(out, "at the start of history")?;
+                        break;
+                    }
+                }
+                self.print_next(out)?;
+            }
+            "bw" => match address(0) {
+                Some(address) => {
+                    self.halted = false;
+                    if !self.machine.back_to_write(address) {
+                        writeln!(out, "no write to {} in history", address)?;
+                    }
+                    self.print_next(out)?;
+                }
+                None => writeln!(out, "bw needs an address")?,
+            },
+            "bo" => {
+                self.halted = false;
+                if !self.machine.back_to_output() {
+                    writeln!(out, "no output in history")?;
+                }
+                self.print_next(out)?;
+            }
+            "q" | "quit" => return Ok(false),
+            _ => writeln!(out, "unknown command {}", command)?,
+        }
+
+        Ok(true)
+    }
+
+    // Runs a single instruction, checking watchpoints around it.
+    fn step(&mut self) -> Option<Stop> {
+        if self.halted {
+            return Some(Stop::Halted);
+        }
+
+        let watched: Vec<(usize, i64)> = self
+            .watchpoints
+            .iter()
+            .map(|address| (*address, self.machine.read(*address)))
+            .collect();
+
+        let stop = match self.machine.step() {
+            Ok(None) => None,
+            Ok(Some(Status::Output(value))) => Some(Stop::Output(value)),
+            Ok(Some(Status::NeedsInput)) => return Some(Stop::NeedsInput),
+            Ok(Some(Status::Halted)) => {
+                self.halted = true;
+                return Some(Stop::Halted);
+            }
+            Err(err) => return Some(Stop::Error(err.to_string())),
+        };
+
+        for (address, old) in watched {
+            let new = self.machine.read(address);
+            if new != old {
+                return Some(Stop::Watchpoint { address, old, new });
+            }
+        }
+
+        stop
+    }
+
+    fn back(&mut self) -> bool {
+        self.halted = false;
+        self.machine.step_back()
+    }
+
+    fn continue_run(&mut self) -> Stop {
+        loop {
+            if let Some(stop) = self.step() {
+                return stop;
+            }
+            let pc = self.machine.pc();
+            if self.breakpoints.contains(&pc) {
+                return Stop::Breakpoint(pc);
+            }
+        }
+    }
+
+    fn print_stop<W: Write>(&self, stop: &Stop, out: &mut W) -> io::Result<()> {
+        match stop {
+            Stop::Output(value) => writeln!(out, "output: {}", value),
+            Stop::NeedsInput => writeln!(out, "waiting for input"),
+            Stop::Halted => writeln!(out, "halted"),
+            Stop::Breakpoint(address) => writeln!(out, "breakpoint at {}", address),
+            Stop::Watchpoint { address, old, new } => {
+                writeln!(out, "watchpoint [{}]: {} -> {}", address, old, new)
+            }
+            Stop::Error(err) => writeln!(out, "error: {}", err),
+        }
+    }
+
+    fn print_next<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        self.print_at(self.machine.pc(), out).map(|_| ())
+    }
+
+    // Prints the instruction at address, returning how many words it takes.
+    fn print_at<W: Write>(&self, address: usize, out: &mut W) -> io::Result<usize> {
+        let memory = self.machine.memory();
+        let marker = if address == self.machine.pc() {
+            "=>"
+        } else {
+            "  "
+        };
+        match decode(memory, address) {
+            Some(line) => {
+                writeln!(out, "{} {:>5}: {}", marker, address, line.text)?;
+                Ok(line.words.len())
+            }
+            None => {
+                let value = self.machine.read(address);
+                writeln!(out, "{} {:>5}: DATA {}", marker, address, value)?;
+                Ok(1)
+            }
+        }
+    }
+}
+
+fn toggle<W: Write>(
+    set: &mut BTreeSet<usize>,
+    address: usize,
+    name: &str,
+    out: &mut W,
+) -> io::Result<()> {
+    if set.remove(&address) {
+        writeln!(out, "cleared {} at {}", name, address)
+    } else {
+        set.insert(address);
+        writeln!(out, "set {} at {}", name, address)
+    }
+}
+
+// Debugs a machine from the terminal.
+pub fn debug_stdin(machine: Machine) -> io::Result<Machine> {
+    let mut debugger = Debugger::new(machine);
+    let stdin = io::stdin();
+    debugger.run(stdin.lock(), io::stdout())?;
+
+    Ok(debugger.into_machine())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(program: &[i64], commands: &str) -> (String, Machine) {
+        let mut debugger = Debugger::new(Machine::new(program));
+        let mut out = vec![];
+        debugger.run(commands.as_bytes(), &mut out).unwrap();
+
+        (String::from_utf8(out).unwrap(), debugger.into_machine())
+    }
+
+    #[test]
+    fn step_and_inspect() {
+        let (out, machine) = session(&[1101, 100, -1, 4, 0], "s\nr\nm 2 3\ns\n");
+
+        assert_eq!(
+            out,
+            concat!(
+                "=>     0: ADD #100 #-1 [4]\n",
+                "> =>     4: HLT\n",
+                "> pc: 4 rb: 0\n",
+                ">     2: -1 4 99\n",
+                "> halted\n",
+                "=>     4: HLT\n",
+                "> ",
+            )
+        );
+        assert_eq!(machine.memory(), &[1101, 100, -1, 4, 99]);
+    }
+
+    #[test]
+    fn breakpoints_watchpoints_and_input() {
+        // the day five "is it zero" jump test
+        let program = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+        let (out, _) = session(&program, "c\ni 7\nw 13\nb 5\nc\nc\nc\nc\nq\nr\n");
+
+        let stops: Vec<&str> = out.lines().filter(|line| !line.contains("=>")).collect();
+        assert_eq!(
+            stops,
+            vec![
+                "> waiting for input",
+                "> > set watchpoint at 13",
+                "> set breakpoint at 5",
+                "> breakpoint at 5",
+                "> watchpoint [13]: 0 -> 1",
+                "> output: 1",
+                "> halted",
+                "> ",
+            ]
+        );
+    }
+
+    #[test]
+    fn running_backwards() {
+        let program = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+        let (out, machine) = session(&program, "i 7\nc\nc\nbo\nsb 2\nbw 12\nsb\nm 12 2\nc\n");
+
+        assert_eq!(
+            out,
+            concat!(
+                "=>     0: IN [12]\n",
+                "> > output: 1\n",
+                "=>    11: HLT\n",
+                "> halted\n",
+                "=>    11: HLT\n",
+                "> =>     9: OUT [13]\n",
+                "> =>     2: JF [12] [15]\n",
+                "> =>     0: IN [12]\n",
+                "> at the start of history\n",
+                "=>     0: IN [12]\n",
+                ">    12: -1 0\n",
+                "> output: 1\n",
+                "=>    11: HLT\n",
+                "> ",
+            )
+        );
+        assert_eq!(machine.outputs(), &[1]);
+    }
+
+    #[test]
+    fn off_the_end() {
+        let (out, _) = session(&[99], "l 3\n");
+        assert_eq!(
+            out,
+            concat!(
+                "=>     0: HLT\n",
+                "> =>     0: HLT\n",
+                "       1: DATA 0\n",
+                "       2: DATA 0\n",
+                "> ",
+            )
+        );
+
+        // jumps past the end of the program
+        let (out, _) = session(&[1105, 1, 100], "s\ns\n");
+        assert!(out.contains("> =>   100: DATA 0\n> error: unknown opcode 0 at 100\n"));
+
+        let (out, _) = session(&[99], "m -1\nm 0 -1\ni -1\nm 0 2\n");
+        assert_eq!(
+            out,
+            concat!(
+                "=>     0: HLT\n",
+                "> addresses and counts can't be negative\n",
+                "> addresses and counts can't be negative\n",
+                "> >     0: 99 0\n",
+                "> ",
+            )
+        );
+
+        let huge = "m 0 9223372036854775807\ns 9223372036854775807\nl 100001\nsb 100001\n";
+        let (out, _) = session(&[99], &format!("{}m 9223372036854775807 2\n", huge));
+        assert_eq!(
+            out,
+            concat!(
+                "=>     0: HLT\n",
+                "> counts can't be more than 100000\n",
+                "> counts can't be more than 100000\n",
+                "> counts can't be more than 100000\n",
+                "> counts can't be more than 100000\n",
+                "> 9223372036854775807: 0 0\n",
+                "> ",
+            )
+        );
+    }
+}
@@ -0,0 +1,288 @@
+// Instructions decoded ahead of time, opcode, modes and parameter words
+// together, so running one is a lookup instead of reading its words and
+// taking the first one apart. A cache is made once from a program and can be
+// handed to any number of machines loaded with that program, on any thread.
+//
+// Each machine using a cache keeps track of which cached instructions it has
+// written over, and decodes those from memory as usual, so self modifying
+// programs still run as written. The cache is off unless asked for: making
+// one decodes the whole program, which only pays off once instructions run
+// many times, in a long running program or across many runs of one.
+use std::sync::Arc;
+
+use super::{parse_code, ArgMode, IntcodeError, Machine, Memory, OpCode, Tracer};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decoded {
+    pub opcode: OpCode,
+    pub modes: [ArgMode; 3],
+    // only the first opcode.arity() are used, the rest are zero
+    pub params: [i64; 3],
+}
+
+// Decodes the instruction at pc in memory.
+fn decode_at<F: Fn(usize) -> i64>(pc: usize, read: F) -> Result<Decoded, IntcodeError> {
+    let (opcode, m1, m2, m3) = parse_code(pc, read(pc))?;
+    let mut params = [0; 3];
+    for (i, param) in params.iter_mut().enumerate().take(opcode.arity()) {
+        *param = read(pc + 1 + i);
+    }
+
+    Ok(Decoded {
+        opcode,
+        modes: [m1, m2, m3],
+        params,
+    })
+}
+
+#[derive(Clone)]
+pub struct DecodeCache {
+    // the program it was made from, loaded and ready to copy
+    memory: Arc<Memory>,
+    // by address, None where there's no valid instruction
+    instructions: Arc<[Option<Decoded>]>,
+}
+
+impl DecodeCache {
+    pub fn new(program: &[i64]) -> DecodeCache {
+        let read = |address: usize| program.get(address).cloned().unwrap_or(0);
+        let instructions = (0..program.len())
+            .map(|pc| decode_at(pc, read).ok())
+            .collect();
+
+        DecodeCache {
+            memory: Arc::new(Memory::new(program)),
+            instructions,
+        }
+    }
+}
+
+// A machine's use of a cache.
+pub(super) struct Decoding {
+    cache: DecodeCache,
+    // a bit for each cached address whose instruction has been written over
+    stale: Vec<u64>,
+}
+
+impl Decoding {
+    fn new(cache: DecodeCache) -> Decoding {
+        let words = cache.instructions.len().div_ceil(64);
+        Decoding {
+            cache,
+            stale: vec![0; words],
+        }
+    }
+
+    fn get(&self, pc: usize) -> Option<Decoded> {
+        let decoded = (*self.cache.instructions.get(pc)?)?;
+        if self.stale[pc / 64] & (1 << (pc % 64)) != 0 {
+            return None;
+        }
+        Some(decoded)
+    }
+
+    // Marks the cached instructions with a word at address as stale.
+    pub(super) fn written(&mut self, address: usize) {
+        for pc in address.saturating_sub(3)..=address {
+            if let Some(Some(decoded)) = self.cache.instructions.get(pc) {
+                if pc + decoded.opcode.arity() >= address {
+                    self.stale[pc / 64] |= 1 << (pc % 64);
+                }
+            }
+        }
+    }
+}
+
+impl Machine {
+    // A machine loaded with the program a cache was made from and using the
+    // cache. Quicker than new and share_decoding, as the program comes
+    // already loaded.
+    pub fn from_cache(cache: &DecodeCache) -> Machine {
+        let mut machine = Machine::new(&[]);
+        machine.memory = (*cache.memory).clone();
+        machine.decoded = Some(Decoding::new(cache.clone()));
+        machine
+    }
+}
+
+impl<T: Tracer> Machine<T> {
+    // Turns the decode cache on, decoding memory as it is now, or off.
+    pub fn cache_decoding(&mut self, on: bool) {
+        self.decoded = if on {
+            Some(Decoding::new(DecodeCache::new(self.memory())))
+        } else {
+            None
+        };
+    }
+
+    // This machine's decode cache, to hand to other machines running the
+    // same program with share_decoding.
+    pub fn decode_cache(&self) -> Option<DecodeCache> {
+        self.decoded.as_ref().map(|decoding| decoding.cache.clone())
+    }
+
+    // Uses a cache made from another copy of this program. Panics if memory
+    // doesn't hold the program the cache was made from.
+    pub fn share_decoding(&mut self, cache: DecodeCache) {
+        assert!(
+            cache.memory.content_hash() == self.memory.content_hash(),
+            "decode cache made from a different program"
+        );
+        self.decoded = Some(Decoding::new(cache));
+    }
+
+    pub(super) fn decode(&self, pc: usize) -> Result<Decoded, IntcodeError> {
+        match self.decoded.as_ref().and_then(|decoding| decoding.get(pc)) {
+            Some(decoded) => Ok(decoded),
+            None => decode_at(pc, |address| self.memory.read(address)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::five;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn self_modifying() {
+        // the loop body at 4 is an ADD the first time round and gets
+        // rewritten into a MUL for the second: 1 + 2 = 3, then 3 * 2 = 6
+        let program = vec![
+            1101, 1, 0, 30, // [30] = 1
+            1, 30, 31, 30, // [30] = [30] + [31]
+            1001, 32, 1, 32, // [32] += 1
+            1008, 32, 2, 33, // [33] = [32] == 2
+            1005, 33, 26, // out if so
+            1101, 0, 2, 4, // otherwise make 4 a MUL
+            1105, 1, 4, // and go round again
+            4, 30, 99, 0, 0, 2, 0, 0,
+        ];
+
+        let mut machine = Machine::new(&program);
+        machine.cache_decoding(true);
+        assert_eq!(machine.run(), Ok(vec![6]));
+
+        // a second machine sharing the first one's cache starts out with
+        // address 4 cached as an ADD again
+        let mut shared = Machine::new(&program);
+        shared.share_decoding(machine.decode_cache().unwrap());
+        assert_eq!(shared.run(), Ok(vec![6]));
+
+        let mut uncached = Machine::new(&program);
+        assert_eq!(uncached.run(), Ok(vec![6]));
+    }
+
+    #[test]
+    fn parameters_written() {
+        // changes the OUT at 4 from [9] to [10] before it runs
+        let program = vec![1101, 10, 0, 5, 4, 9, 99, 0, 0, 111, 222];
+        let mut machine = Machine::new(&program);
+        machine.cache_decoding(true);
+
+        assert_eq!(machine.run(), Ok(vec![222]));
+    }
+
+    #[test]
+    #[should_panic(expected = "different program")]
+    fn other_programs() {
+        let cache = DecodeCache::new(&[1101, 1, 1, 0, 99]);
+        Machine::new(&[1101, 1, 2, 0, 99]).share_decoding(cache);
+    }
+
+    #[test]
+    fn data_next_to_code() {
+        // counts down in the word just after the loop
+        let program = vec![1001, 8, -1, 8, 1005, 8, 0, 99, 3];
+        let mut machine = Machine::new(&program);
+        machine.cache_decoding(true);
+        assert_eq!(machine.run(), Ok(vec![]));
+
+        // the loop is still cached, only the IN that the counter's first
+        // value looks like has gone stale
+        let decoding = machine.decoded.as_ref().unwrap();
+        assert_eq!(decoding.get(0).unwrap().opcode, OpCode::Add);
+        assert_eq!(decoding.get(4).unwrap().opcode, OpCode::JumpIfTrue);
+        assert_eq!(decoding.get(7).unwrap().opcode, OpCode::Halt);
+        assert_eq!(decoding.get(8), None);
+    }
+
+    #[test]
+    fn sendable() {
+        // sweeps hand caches to other threads, and machines can go with them
+        fn shareable<T: Send + Sync>() {}
+        fn sendable<T: Send>() {}
+        shareable::<DecodeCache>();
+        sendable::<Machine>();
+    }
+
+    // The best of five timings, the machine this runs on is rarely quiet.
+    fn best_of<F: FnMut()>(mut run: F) -> Duration {
+        (0..5)
+            .map(|_| {
+                let start = Instant::now();
+                run();
+                start.elapsed()
+            })
+            .min()
+            .unwrap()
+    }
+
+    fn report(title: &str, uncached: Duration, cached: Duration) {
+        println!("{}:", title);
+        println!("  decoding every step  {:?}", uncached);
+        println!(
+            "  cached               {:?} ({:.2}x)",
+            cached,
+            uncached.as_secs_f64() / cached.as_secs_f64()
+        );
+    }
+
+    // cargo test --release bench_ -- --ignored --nocapture --test-threads 1
+    #[test]
+    #[ignore]
+    fn bench_five() {
+        let program = five::load_input();
+        let cache = DecodeCache::new(&program);
+        let time = |cached: bool| {
+            best_of(|| {
+                for _ in 0..100_000 {
+                    let mut machine = if cached {
+                        Machine::from_cache(&cache)
+                    } else {
+                        Machine::new(&program)
+                    };
+                    machine.provide_input(5);
+                    assert_eq!(machine.run(), Ok(vec![3176266]));
+                }
+            })
+        };
+
+        report(
+            "100000 runs of the day 5 diagnostic sharing a cache",
+            time(false),
+            time(true),
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_loop() {
+        // the countdown loop above, from five million
+        let program = vec![1001, 8, -1, 8, 1005, 8, 0, 99, 5_000_000];
+        let time = |cache: bool| {
+            best_of(|| {
+                let mut machine = Machine::new(&program);
+                machine.cache_decoding(cache);
+                assert_eq!(machine.run(), Ok(vec![]));
+            })
+        };
+
+        report(
+            "10 million instructions of one loop",
+            time(false),
+            time(true),
+        );
+    }
+}
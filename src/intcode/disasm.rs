@@ -0,0 +1,134 @@
+use super::{parse_code, ArgMode, OpCode};
+
+impl OpCode {
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            OpCode::Add => "ADD",
+            OpCode::Mult => "MUL",
+            OpCode::Input => "IN",
+            OpCode::Output => "OUT",
+            OpCode::JumpIfTrue => "JT",
+            OpCode::JumpIfFalse => "JF",
+            OpCode::LessThan => "LT",
+            OpCode::Equals => "EQ",
+            OpCode::AdjustRelativeBase => "ARB",
+            OpCode::Halt => "HLT",
+        }
+    }
+}
+
+// How a parameter is written in a listing: [225] reads address 225, #9 is
+// the value 9, and [rb+3] reads three past the relative base.
+pub fn format_param(mode: ArgMode, param: i64) -> String {
+    match mode {
+        ArgMode::Position => format!("[{}]", param),
+        ArgMode::Immediate => format!("#{}", param),
+        ArgMode::Relative if param < 0 => format!("[rb{}]", param),
+        ArgMode::Relative => format!("[rb+{}]", param),
+    }
+}
+
+// One line of a listing, either a decoded instruction or a word of data.
+#[derive(Debug, PartialEq)]
+pub struct Line {
+    pub address: usize,
+    pub words: Vec<i64>,
+    pub text: String,
+}
+
+// Decodes the instruction at address, or None if the word there isn't a
+// valid instruction, it or its parameters run off the end of the program.
+pub fn decode(program: &[i64], address: usize) -> Option<Line> {
+    let (opcode, m1, m2, m3) = parse_code(address, *program.get(address)?).ok()?;
+    let arity = opcode.arity();
+    if address + arity >= program.len() {
+        return None;
+    }
+
+    let modes = [m1, m2, m3];
+    let params = &program[address + 1..=address + arity];
+    let mut text = opcode.mnemonic().to_string();
+    for (mode, param) in modes.iter().zip(params) {
+        text.push(' ');
+        text.push_str(&format_param(*mode, *param));
+    }
+
+    Some(Line {
+        address,
+        words: program[address..=address + arity].to_vec(),
+        text,
+    })
+}
+
+// Walks the program from the start, decoding each instruction and moving on
+// past its parameters. Words that don't decode are listed as DATA.
+pub fn disassemble(program: &[i64]) -> Vec<Line> {
+    let mut lines = vec![];
+
+    let mut address = 0;
+    while address < program.len() {
+        let line = decode(program, address).unwrap_or_else(|| Line {
+            address,
+            words: vec![program[address]],
+            text: format!("DATA {}", program[address]),
+        });
+
+        address += line.words.len();
+        lines.push(line);
+    }
+
+    lines
+}
+
+pub fn listing(program: &[i64]) -> String {
+    let mut out = String::new();
+    for line in disassemble(program) {
+        out.push_str(&format!("{:>5}: {}\n", line.address, line.text));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_listing() {
+        assert_eq!(
+            listing(&[1002, 4, 3, 4, 33]),
+            "    0: MUL [4] #3 [4]\n    4: DATA 33\n"
+        );
+        assert_eq!(
+            listing(&[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]),
+            concat!(
+                "    0: IN [9]\n",
+                "    2: EQ [9] [10] [9]\n",
+                "    6: OUT [9]\n",
+                "    8: HLT\n",
+                "    9: DATA -1\n",
+                "   10: DATA 8\n",
+            )
+        );
+    }
+
+    #[test]
+    fn modes_and_data() {
+        let lines = disassemble(&[109, -1, 1206, -3, 7, 22201, 1, 2, 3, 1105, 0]);
+
+        assert_eq!(lines[0].text, "ARB #-1");
+        assert_eq!(lines[1].text, "JF [rb-3] #7");
+        assert_eq!(lines[1].words, vec![1206, -3, 7]);
+        assert_eq!(lines[2].text, "ADD [rb+1] [rb+2] [rb+3]");
+        // a jump with no room for its parameters is just data
+        assert_eq!(lines[3].text, "DATA 1105");
+        assert_eq!(lines[4].text, "DATA 0");
+        assert_eq!(lines.len(), 5);
+    }
+
+    #[test]
+    fn past_the_end() {
+        assert_eq!(decode(&[99], 1), None);
+        assert_eq!(decode(&[], 0), None);
+    }
+}
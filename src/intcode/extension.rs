@@ -0,0 +1,288 @@
+// Host defined opcodes. A host registers an opcode number that isn't
+// already taken with its arity, which parameter (if any) it writes to, and
+// a handler. Parameters are read with the usual modes; the handler gets the
+// value of each read parameter (and the address of the written one) and
+// says what the instruction does.
+//
+//     machine.register_opcode(10, Extension::new("MOD", 3, |args| {
+//         Ok(Effect::write(args[0] % args[1]))
+//     }).writes(2));
+use super::trace::ExtensionStep;
+use super::{parse_mode, IntcodeError, Machine, OpCode, Status, Tracer};
+
+// What an extension instruction does, beyond moving on to the next one.
+#[derive(Debug, Default, PartialEq)]
+pub struct Effect {
+    // stored at the write parameter's address
+    pub write: Option<i64>,
+    // where to carry on instead of the following instruction
+    pub jump: Option<i64>,
+    pub output: Option<i64>,
+}
+
+impl Effect {
+    pub fn write(value: i64) -> Effect {
+        Effect {
+            write: Some(value),
+            ..Effect::default()
+        }
+    }
+
+    pub fn jump(address: i64) -> Effect {
+        Effect {
+            jump: Some(address),
+            ..Effect::default()
+        }
+    }
+
+    pub fn output(value: i64) -> Effect {
+        Effect {
+            output: Some(value),
+            ..Effect::default()
+        }
+    }
+}
+
+type Handler = Box<dyn FnMut(&[i64]) -> Result<Effect, String> + Send>;
+
+pub struct Extension {
+    name: String,
+    arity: usize,
+    write_param: Option<usize>,
+    handler: Handler,
+}
+
+impl Extension {
+    pub fn new<F>(name: &str, arity: usize, handler: F) -> Extension
+    where
+        F: FnMut(&[i64]) -> Result<Effect, String> + Send + 'static,
+    {
+        Extension {
+            name: name.to_string(),
+            arity,
+            write_param: None,
+            handler: Box::new(handler),
+        }
+    }
+
+    // Marks the parameter the instruction writes to, counting from 0.
+    pub fn writes(mut self, param: usize) -> Extension {
+        assert!(param < self.arity, "write parameter past the end");
+        self.write_param = Some(param);
+        self
+    }
+}
+
+impl<T: Tracer> Machine<T> {
+    // Adds an opcode. Panics if code is already a built in opcode or isn't
+    // something the low two digits of an instruction can hold.
+    pub fn register_opcode(&mut self, code: i64, extension: Extension) {
+        assert!(code > 0 && code < 100, "opcode {} out of range", code);
+        assert!(
+            OpCode::ALL.iter().all(|opcode| opcode.code() != code),
+            "opcode {} is built in",
+            code
+        );
+
+        self.extensions.insert(code, extension);
+    }
+
+    pub(super) fn step_extension(&mut self, code: i64) -> Result<Option<Status>, IntcodeError> {
+        let pc = self.pc;
+        let relative_base = self.relative_base;
+        let extension = &self.extensions[&code];
+        let (arity, write_param) = (extension.arity, extension.write_param);
+
+        let mut modes = vec![];
+        let mut params = vec![];
+        let mut args = vec![];
+        let mut mode_digits = self.memory.read(pc) / 100;
+        for i in 0..arity {
+            let mode = parse_mode(pc, mode_digits % 10)?;
+            mode_digits /= 10;
+
+            let param = self.memory.read(pc + 1 + i);
+            modes.push(mode);
+            params.push(param);
+            if write_param == Some(i) {
+                args.push(self.get_dest(param, mode)? as i64);
+            } else {
+                args.push(self.get_arg(param, mode)?);
+            }
+        }
+        // and there are no modes for parameters it doesn't have
+        if mode_digits != 0 {
+            return Err(IntcodeError::InvalidMode {
+                pc,
+                mode: mode_digits,
+            });
+        }
+
+        let extension = self.extensions.get_mut(&code).unwrap();
+        let effect = (extension.handler)(&args)
+            .map_err(|message| IntcodeError::ExtensionFailed { pc, code, message })?;
+
+        let write = match (write_param, effect.write) {
+            (Some(param), Some(value)) => Some((args[param] as usize, value)),
+            (None, Some(_)) => {
+                return Err(IntcodeError::ExtensionFailed {
+                    pc,
+                    code,
+                    message: "wrote a value with no parameter to write it to".to_string(),
+                })
+            }
+            (_, None) => None,
+        };
+        self.pc = match effect.jump {
+            Some(address) => self.address(address)?,
+            None => pc + 1 + arity,
+        };
+        if let Some(value) = effect.output {
+            self.output.push(value);
+        }
+        self.commit(
+            pc,
+            relative_base,
+            write,
+            None,
+            effect.output.is_some(),
+            false,
+        )?;
+
+        let name = self.extensions[&code].name.clone();
+        self.tracer.trace_extension(&ExtensionStep {
+            pc,
+            code,
+            name,
+            modes,
+            params,
+            args,
+            write_param,
+            write,
+        });
+
+        Ok(effect.output.map(Status::Output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::trace::TextTracer;
+    use std::sync::{Arc, Mutex};
+
+    fn modulo() -> Extension {
+        Extension::new("MOD", 3, |args| {
+            if args[1] == 0 {
+                Err("modulo by zero".to_string())
+            } else {
+                Ok(Effect::write(args[0] % args[1]))
+            }
+        })
+        .writes(2)
+    }
+
+    #[test]
+    fn modulo_opcode() {
+        // MOD [0] #7 [5], then outputs it
+        let mut machine = Machine::new(&[1010, 8, 7, 9, 4, 9, 99, 0, 45, 0]);
+        machine.register_opcode(10, modulo());
+
+        assert_eq!(machine.run(), Ok(vec![3]));
+
+        let mut machine = Machine::new(&[1110, 8, 0, 9, 99]);
+        machine.register_opcode(10, modulo());
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::ExtensionFailed {
+                pc: 0,
+                code: 10,
+                message: "modulo by zero".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn host_calls_and_jumps() {
+        // the host keeps a running total of whatever is passed to opcode 20,
+        // and opcode 21 outputs it then jumps to the halt
+        let total = Arc::new(Mutex::new(0));
+        let mut machine = Machine::new(&[120, 5, 120, 6, 21, 7, 99]);
+
+        let adder = Arc::clone(&total);
+        machine.register_opcode(
+            20,
+            Extension::new("ACC", 1, move |args| {
+                *adder.lock().unwrap() += args[0];
+                Ok(Effect::default())
+            }),
+        );
+        let reader = Arc::clone(&total);
+        machine.register_opcode(
+            21,
+            Extension::new("TOTAL", 0, move |_| {
+                Ok(Effect {
+                    output: Some(*reader.lock().unwrap()),
+                    jump: Some(6),
+                    ..Effect::default()
+                })
+            }),
+        );
+
+        assert_eq!(machine.run(), Ok(vec![11]));
+        assert_eq!(*total.lock().unwrap(), 11);
+    }
+
+    #[test]
+    fn misused() {
+        // a write with nowhere to go
+        let mut machine = Machine::new(&[20, 0, 99]);
+        machine.register_opcode(20, Extension::new("SET", 1, |_| Ok(Effect::write(5))));
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::ExtensionFailed {
+                pc: 0,
+                code: 20,
+                message: "wrote a value with no parameter to write it to".to_string()
+            })
+        );
+        assert_eq!(machine.read(0), 20);
+
+        // modes for parameters NOP doesn't have
+        let mut machine = Machine::new(&[11120, 5, 99]);
+        machine.register_opcode(20, Extension::new("NOP", 1, |_| Ok(Effect::default())));
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::InvalidMode { pc: 0, mode: 11 })
+        );
+    }
+
+    #[test]
+    fn traced_and_undone() {
+        let mut machine =
+            Machine::new(&[1010, 8, 7, 9, 4, 9, 99, 0, 45, 0]).with_tracer(TextTracer::new(vec![]));
+        machine.register_opcode(10, modulo());
+        machine.record_history();
+
+        assert_eq!(machine.run(), Ok(vec![3]));
+        assert!(machine.step_back());
+        assert!(machine.step_back());
+        assert_eq!(machine.read(9), 0);
+
+        let text = machine.into_tracer().finish().unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            concat!(
+                "    0: MOD [8] #7 [9]          45 7 -> [9] = 3\n",
+                "    4: OUT [9]                 3\n",
+                "    6: HLT\n",
+            )
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "opcode 1 is built in")]
+    fn no_replacing_builtins() {
+        Machine::new(&[99]).register_opcode(1, modulo());
+    }
+}
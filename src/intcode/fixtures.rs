@@ -0,0 +1,14 @@
+// Programs used by the tests of more than one module.
+
+// The larger day 5 example: outputs 999 for an input below 8, 1000 for 8
+// and 1001 above.
+pub const COMPARE: [i64; 47] = [
+    3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
+    1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105,
+    1, 46, 98, 99,
+];
+
+// Doubles each input until it reads a zero.
+pub const DOUBLER: [i64; 16] = [
+    3, 15, 1006, 15, 14, 1002, 15, 2, 15, 4, 15, 1105, 1, 0, 99, 0,
+];
@@ -0,0 +1,417 @@
+// Property testing for the interpreters: random programs, checks run over
+// each of them, and shrinking of any program that fails a check down to a
+// small one that still does. Everything is driven by a seed, so a failure
+// can be reproduced from the case number it reports.
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use super::memory::mix;
+use super::OpCode;
+
+// splitmix64, small and good enough for making up programs.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.state)
+    }
+
+    // A number in 0..n.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    // A number in low..=high.
+    pub fn between(&mut self, low: i64, high: i64) -> i64 {
+        low + (self.next_u64() % (high - low + 1) as u64) as i64
+    }
+}
+
+// A day 2 style program: instructions Add and Mult reading and writing
+// addresses inside the program, then a halt and a few words of data. It can
+// still write over its own instructions, so it doesn't always run cleanly.
+pub fn day2_program(rng: &mut Rng, instructions: usize) -> Vec<i64> {
+    let len = instructions * 4 + 1 + 4;
+
+    let mut program = vec![];
+    for _ in 0..instructions {
+        program.push(if rng.below(2) == 0 { 1 } else { 2 });
+        for _ in 0..3 {
+            program.push(rng.below(len) as i64);
+        }
+    }
+    program.push(99);
+    while program.len() < len {
+        program.push(rng.between(-9, 99));
+    }
+
+    program
+}
+
+// Words that are mostly instructions, with any opcode and modes and small
+// parameters, so that they jump around, read input, use relative mode and
+// go wrong in every way there is.
+pub fn mode_program(rng: &mut Rng, len: usize) -> Vec<i64> {
+    let mut program = vec![];
+    while program.len() < len {
+        if rng.below(10) == 0 {
+            program.push(rng.between(-50, 2000));
+            continue;
+        }
+
+        let opcode = OpCode::ALL[rng.below(OpCode::ALL.len())];
+        let mut word = opcode.code();
+        for place in &[100, 1000, 10_000] {
+            word += place * rng.between(0, 2);
+        }
+        program.push(word);
+        for _ in 0..opcode.arity() {
+            program.push(rng.between(-3, len as i64 + 3));
+        }
+    }
+
+    program
+}
+
+// Removes words from program and makes the rest closer to zero for as long
+// as fails keeps returning true, giving a smaller program that still fails.
+pub fn shrink<F: Fn(&[i64]) -> bool>(mut program: Vec<i64>, fails: F) -> Vec<i64> {
+    loop {
+        let mut smaller = false;
+
+        // cut out runs of words, longest first
+        let mut run = program.len() / 2;
+        while run > 0 {
+            let mut start = 0;
+            while start + run <= program.len() {
+                let mut candidate = program.clone();
+                candidate.drain(start..start + run);
+                if fails(&candidate) {
+                    program = candidate;
+                    smaller = true;
+                } else {
+                    start += 1;
+                }
+            }
+            run /= 2;
+        }
+
+        // then bring each word as close to zero as it can go
+        for i in 0..program.len() {
+            let word = program[i];
+            let mut step = word / 2;
+            let mut tries = vec![0];
+            while step != 0 {
+                tries.push(word - step);
+                step /= 2;
+            }
+
+            for value in tries.into_iter().filter(|value| *value != word) {
+                let mut candidate = program.clone();
+                candidate[i] = value;
+                if fails(&candidate) {
+                    program = candidate;
+                    smaller = true;
+                    break;
+                }
+            }
+        }
+
+        if !smaller {
+            return program;
+        }
+    }
+}
+
+// A program that failed a check, made as small as it would go.
+#[derive(Debug)]
+pub struct Failure {
+    pub case: u64,
+    pub original: Vec<i64>,
+    pub program: Vec<i64>,
+    pub message: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "case {} failed: {}\n  shrunk: {:?}\n  original: {:?}",
+            self.case, self.message, self.program, self.original
+        )
+    }
+}
+
+// Runs a check, turning a panic into a failure like any other.
+fn run_check<C>(check: &C, program: &[i64]) -> Result<(), String>
+where
+    C: Fn(&[i64]) -> Result<(), String>,
+{
+    match panic::catch_unwind(AssertUnwindSafe(|| check(program))) {
+        Ok(result) => result,
+        Err(panic) => Err(match panic.downcast_ref::<&str>() {
+            Some(message) => format!("panicked: {}", message),
+            None => match panic.downcast_ref::<String>() {
+                Some(message) => format!("panicked: {}", message),
+                None => "panicked".to_string(),
+            },
+        }),
+    }
+}
+
+// Generates a program for each case and runs check on it, stopping at the
+// first one that fails. Case n uses the seed n.
+pub fn check_cases<G, C>(cases: u64, generate: G, check: C) -> Result<(), Failure>
+where
+    G: Fn(&mut Rng) -> Vec<i64>,
+    C: Fn(&[i64]) -> Result<(), String>,
+{
+    for case in 0..cases {
+        let original = generate(&mut Rng::new(case));
+        if run_check(&check, &original).is_err() {
+            let program = shrink(original.clone(), |program| {
+                run_check(&check, program).is_err()
+            });
+            let message = run_check(&check, &program).unwrap_err();
+
+            return Err(Failure {
+                case,
+                original,
+                program,
+                message,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{symbolic, ArgMode, IntcodeError, Machine};
+    use crate::two;
+    use std::convert::TryInto;
+
+    fn assert_passes(result: Result<(), Failure>) {
+        if let Err(failure) = result {
+            panic!("{}", failure);
+        }
+    }
+
+    // The day 2 interpreter from before there was a Machine, kept as an
+    // oracle. two::run_program and five::run_program both run on Machine, so
+    // comparing them only checks Machine against itself; this loop shares no
+    // code with it. It panicked on anything out of range or overflowing,
+    // which is None here.
+    fn baseline_day2(program: Vec<i64>) -> Option<Vec<i64>> {
+        let mut prog = program;
+
+        let mut pc = 0;
+
+        loop {
+            let opcode = *prog.get(pc)?;
+            match opcode {
+                1 | 2 => {
+                    let a_i: usize = (*prog.get(pc + 1)?).try_into().ok()?;
+                    let b_i: usize = (*prog.get(pc + 2)?).try_into().ok()?;
+                    let dest: usize = (*prog.get(pc + 3)?).try_into().ok()?;
+
+                    let (a, b) = (*prog.get(a_i)?, *prog.get(b_i)?);
+                    *prog.get_mut(dest)? = if opcode == 1 {
+                        a.checked_add(b)?
+                    } else {
+                        a.checked_mul(b)?
+                    };
+                }
+                99 => {
+                    // exit
+                    break;
+                }
+                _ => return None,
+            }
+
+            pc += 4
+        }
+
+        Some(prog)
+    }
+
+    // Runs a day 2 program through every interpreter that takes one.
+    fn day2_agree(program: &[i64]) -> Result<(), String> {
+        // shrinking, or the program writing over itself, can turn a day 2
+        // program into one that uses modes, reads input or jumps, maybe
+        // forever, and those aren't compared
+        let mut traced = Machine::new(program).with_tracer(vec![]);
+        traced.set_budget(Some(1000));
+        let result = traced.run();
+        let day2 = [OpCode::Add, OpCode::Mult, OpCode::Halt];
+        let beyond = traced.tracer().iter().any(|step| {
+            !day2.contains(&step.opcode) || step.modes.iter().any(|mode| *mode != ArgMode::Position)
+        });
+        if beyond
+            || matches!(
+                result,
+                Err(IntcodeError::InputExhausted { .. })
+                    | Err(IntcodeError::BudgetExhausted { .. })
+            )
+        {
+            return Ok(());
+        }
+
+        // programs the baseline couldn't run, reading past the end say, are
+        // still compared with the symbolic runner below
+        let two = two::run_program(program.to_vec());
+        if let Some(baseline) = baseline_day2(program.to_vec()) {
+            if two.as_ref() != Ok(&baseline) {
+                return Err(format!("two gave {:?}, baseline gave {:?}", two, baseline));
+            }
+        }
+
+        // the symbolic runner is written separately, with no unknowns it is
+        // a third interpreter to compare against, and a cell it couldn't
+        // work out is as much a failure as the whole run
+        let symbolic = symbolic::run(program, &[]).and_then(|memory| {
+            memory
+                .into_iter()
+                .map(|value| value.map(|linear| linear.constant))
+                .collect::<Result<Vec<i64>, _>>()
+        });
+        match (&two, symbolic) {
+            (Ok(two), Ok(symbolic)) if *two != symbolic => {
+                Err(format!("two gave {:?}, symbolic gave {:?}", two, symbolic))
+            }
+            (Ok(_), Err(err)) => Err(format!("only symbolic failed: {}", err)),
+            (Err(err), Ok(_)) => Err(format!("only two failed: {}", err)),
+            _ => Ok(()),
+        }
+    }
+
+    #[test]
+    fn day2_interpreters_agree() {
+        assert_passes(check_cases(
+            500,
+            |rng| {
+                let instructions = 1 + rng.below(8);
+                day2_program(rng, instructions)
+            },
+            day2_agree,
+        ));
+
+        // squares [13] until it overflows, which every interpreter must
+        // agree on
+        let overflowing = [2, 13, 13, 13, 2, 13, 13, 13, 2, 13, 13, 12, 99, 1000];
+        assert_eq!(
+            two::run_program(overflowing.to_vec()),
+            Err(IntcodeError::Overflow { pc: 8 })
+        );
+        assert_eq!(day2_agree(&overflowing), Ok(()));
+    }
+
+    const BUDGET: u64 = 200;
+
+    // Runs a program with a few inputs and the budget.
+    fn run(program: &[i64], cache: bool) -> (Machine, Result<Vec<i64>, IntcodeError>) {
+        let mut machine = Machine::new(program);
+        machine.cache_decoding(cache);
+        machine.set_budget(Some(BUDGET));
+        machine.record_history();
+        machine.provide_inputs(vec![3, -1, 0, 7]);
+        let result = machine.run();
+
+        (machine, result)
+    }
+
+    #[test]
+    fn invariants() {
+        assert_passes(check_cases(
+            500,
+            |rng| {
+                let len = 4 + rng.below(40);
+                mode_program(rng, len)
+            },
+            |program| {
+                let (mut machine, result) = run(program, true);
+
+                if machine.executed() > BUDGET {
+                    return Err(format!("ran {} instructions", machine.executed()));
+                }
+                match &result {
+                    Err(IntcodeError::BudgetExhausted { executed, .. }) if *executed != BUDGET => {
+                        return Err(format!("budget ran out after {}", executed));
+                    }
+                    Err(err) if err.to_string().is_empty() => {
+                        return Err(format!("{:?} has no message", err));
+                    }
+                    _ => {}
+                }
+
+                // the decode cache changes nothing
+                let (uncached, uncached_result) = run(program, false);
+                if uncached_result != result || uncached.memory() != machine.memory() {
+                    return Err(format!(
+                        "cached run gave {:?}, uncached gave {:?}",
+                        result, uncached_result
+                    ));
+                }
+
+                // and everything can be undone
+                while machine.step_back() {}
+                if machine.pc() != 0
+                    || !machine.outputs().is_empty()
+                    || machine.memory()[..program.len()] != *program
+                    || machine.memory()[program.len()..]
+                        .iter()
+                        .any(|word| *word != 0)
+                {
+                    return Err("stepping back didn't get back to the start".to_string());
+                }
+
+                Ok(())
+            },
+        ));
+    }
+
+    #[test]
+    fn shrinks() {
+        let failure = check_cases(
+            100,
+            |rng| (0..20).map(|_| rng.between(-20, 300)).collect(),
+            |program| {
+                if program.iter().any(|word| *word > 250) {
+                    Err("too big".to_string())
+                } else {
+                    Ok(())
+                }
+            },
+        )
+        .unwrap_err();
+
+        assert_eq!(failure.program, vec![251]);
+        assert_eq!(failure.message, "too big");
+        assert!(failure.original.len() == 20);
+    }
+
+    #[test]
+    fn panics_are_failures() {
+        let failure = check_cases(
+            1,
+            |_| vec![5, 0, 1, 9, 2],
+            |program| {
+                assert!(!program.contains(&9), "found a nine");
+                Ok(())
+            },
+        )
+        .unwrap_err();
+
+        assert_eq!(failure.program, vec![9]);
+        assert_eq!(failure.message, "panicked: found a nine");
+    }
+}
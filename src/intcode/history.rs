@@ -0,0 +1,149 @@
+// Running backwards. Once record_history is called the machine logs what
+// each instruction changed, enough to put it back, so it can step back
+// without running again from the start.
+use super::{Machine, Tracer};
+
+// What one instruction changed, and what it was before.
+pub struct Undo {
+    pub pc: usize,
+    pub relative_base: i64,
+    // the address written and the value it held before
+    pub write: Option<(usize, i64)>,
+    // the input the instruction consumed
+    pub input: Option<i64>,
+    // whether it output a value
+    pub output: bool,
+    // how many instructions had run before it
+    pub executed: u64,
+}
+
+impl<T: Tracer> Machine<T> {
+    // Starts keeping an undo log. Steps taken before this can't be undone.
+    pub fn record_history(&mut self) {
+        if self.history.is_none() {
+            self.history = Some(vec![]);
+        }
+    }
+
+    // How many instructions can be stepped back over.
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, |history| history.len())
+    }
+
+    // Undoes the last instruction, returning what it changed, or None at
+    // the start of the log.
+    fn undo(&mut self) -> Option<Undo> {
+        let undo = self.history.as_mut()?.pop()?;
+
+        self.pc = undo.pc;
+        self.relative_base = undo.relative_base;
+        self.executed = undo.executed;
+        // states seen after this point haven't happened any more
+        let executed = self.executed;
+        if let Some(seen_states) = &mut self.seen_states {
+            seen_states.truncate(executed);
+        }
+        if let Some((address, value)) = undo.write {
+            self.write(address, value);
+        }
+        if let Some(input) = undo.input {
+            self.input.push_front(input);
+        }
+        if undo.output {
+            self.output.pop();
+        }
+
+        Some(undo)
+    }
+
+    pub fn step_back(&mut self) -> bool {
+        self.undo().is_some()
+    }
+
+    // Runs back to just before the last instruction that wrote address.
+    pub fn back_to_write(&mut self, address: usize) -> bool {
+        while let Some(undo) = self.undo() {
+            if let Some((written, _)) = undo.write {
+                if written == address {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    // Runs back to just before the last output was made.
+    pub fn back_to_output(&mut self) -> bool {
+        while let Some(undo) = self.undo() {
+            if undo.output {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::intcode::fixtures::DOUBLER;
+    use crate::intcode::{Machine, Status};
+
+    #[test]
+    fn step_back() {
+        let mut machine = Machine::new(&DOUBLER);
+        machine.record_history();
+        machine.provide_inputs(vec![21, 4]);
+
+        assert_eq!(machine.resume(), Ok(Status::Output(42)));
+        assert_eq!(machine.history_len(), 4);
+
+        // back over the output, then the multiply
+        assert!(machine.step_back());
+        assert_eq!((machine.pc(), machine.outputs()), (9, &[][..]));
+        assert!(machine.step_back());
+        assert_eq!((machine.pc(), machine.read(15)), (5, 21));
+
+        // and forward again
+        assert_eq!(machine.resume(), Ok(Status::Output(42)));
+        assert_eq!(machine.resume(), Ok(Status::Output(8)));
+
+        // all the way back puts both inputs back in the queue
+        while machine.step_back() {}
+        assert_eq!(machine.pc(), 0);
+        assert_eq!(machine.memory(), &DOUBLER[..]);
+        assert_eq!(machine.resume(), Ok(Status::Output(42)));
+        assert_eq!(machine.resume(), Ok(Status::Output(8)));
+    }
+
+    #[test]
+    fn back_to_write_and_output() {
+        let mut machine = Machine::new(&DOUBLER);
+        machine.record_history();
+        machine.provide_inputs(vec![21, 4, 0]);
+        assert_eq!(machine.run(), Ok(vec![42, 8]));
+
+        // the last write to 15 was the zero input
+        assert!(machine.back_to_write(15));
+        assert_eq!((machine.pc(), machine.read(15)), (0, 8));
+
+        assert!(machine.back_to_output());
+        assert_eq!(machine.pc(), 9);
+        assert_eq!(machine.outputs(), &[42]);
+        assert!(machine.back_to_output());
+        assert_eq!(machine.outputs(), &[] as &[i64]);
+        assert!(!machine.back_to_output());
+        assert_eq!(machine.pc(), 0);
+    }
+
+    #[test]
+    fn nothing_to_undo() {
+        let mut machine = Machine::new(&DOUBLER);
+        machine.provide_input(1);
+        machine.resume().unwrap();
+
+        assert!(!machine.step_back());
+        assert_eq!(machine.pc(), 11);
+    }
+}
@@ -0,0 +1,227 @@
+// Guards against programs that never halt: a budget on how many
+// instructions may run, and an opt-in detector for exact repeated states.
+use std::collections::{HashMap, HashSet};
+
+use super::memory::mix;
+use super::{IntcodeError, Machine, Memory, Tracer};
+
+// What loop detection remembers since the last input or output.
+#[derive(Default)]
+pub(super) struct SeenStates {
+    // each state's hash, and the instruction count and registers it was
+    // first seen with
+    seen: HashMap<u64, (u64, usize, i64)>,
+    // every write since, as the instruction count, address and old value
+    writes: Vec<(u64, usize, i64)>,
+}
+
+impl SeenStates {
+    // Forgets everything after the first executed instructions, when the
+    // machine steps back.
+    pub(super) fn truncate(&mut self, executed: u64) {
+        self.seen.retain(|_, (seen, _, _)| *seen <= executed);
+        self.writes.retain(|(count, _, _)| *count < executed);
+    }
+
+    // Whether memory is as it was after the first since instructions: every
+    // cell written from then on holds what it held before the first of
+    // those writes.
+    fn unchanged_since(&self, since: u64, memory: &Memory) -> bool {
+        let mut checked = HashSet::new();
+        self.writes
+            .iter()
+            .filter(|(count, _, _)| *count >= since)
+            .filter(|(_, address, _)| checked.insert(*address))
+            .all(|(_, address, old)| memory.read(*address) == *old)
+    }
+}
+
+impl<T: Tracer> Machine<T> {
+    // Stops the machine with BudgetExhausted once it has run this many
+    // instructions in total.
+    pub fn set_budget(&mut self, budget: Option<u64>) {
+        self.budget = budget;
+    }
+
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    // Starts watching for the machine returning to a state it has already
+    // been in since its last input or output, which means it is stuck in a
+    // loop. States are looked up by hash, and a match is checked against
+    // every write since the state was first seen, so a collision never
+    // stops a program. That costs map and log entries per instruction
+    // between I/O, so this is best kept for tests and tools.
+    pub fn detect_loops(&mut self) {
+        if self.seen_states.is_none() {
+            self.seen_states = Some(SeenStates::default());
+        }
+    }
+
+    pub(super) fn check_budget(&self) -> Result<(), IntcodeError> {
+        match self.budget {
+            Some(budget) if self.executed >= budget => Err(IntcodeError::BudgetExhausted {
+                pc: self.pc,
+                executed: self.executed,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // Called with an instruction's write just before it is made.
+    pub(super) fn log_write(&mut self, address: usize) {
+        if let Some(seen_states) = &mut self.seen_states {
+            let old = self.memory.read(address);
+            seen_states.writes.push((self.executed, address, old));
+        }
+    }
+
+    // Called after every instruction. did_io means the instruction read or
+    // wrote a value, which starts the search over.
+    pub(super) fn check_loop(&mut self, did_io: bool) -> Result<(), IntcodeError> {
+        let seen_states = match &mut self.seen_states {
+            Some(seen_states) => seen_states,
+            None => return Ok(()),
+        };
+        let state = state_hash(self.pc, self.relative_base, &self.memory);
+        let registers = (self.pc, self.relative_base);
+
+        if did_io {
+            seen_states.seen.clear();
+            seen_states.writes.clear();
+        }
+        if let Some(&(first_seen, pc, relative_base)) = seen_states.seen.get(&state) {
+            if (pc, relative_base) == registers
+                && seen_states.unchanged_since(first_seen, &self.memory)
+            {
+                return Err(IntcodeError::InfiniteLoop {
+                    pc: self.pc,
+                    first_seen,
+                    seen_again: self.executed,
+                });
+            }
+        }
+        seen_states
+            .seen
+            .insert(state, (self.executed, self.pc, self.relative_base));
+
+        Ok(())
+    }
+}
+
+fn state_hash(pc: usize, relative_base: i64, memory: &Memory) -> u64 {
+    let registers = mix(pc as u64) ^ mix(relative_base as u64).rotate_left(32);
+    mix(memory.content_hash() ^ registers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budget() {
+        let mut machine = Machine::new(&[1105, 1, 0]);
+        machine.set_budget(Some(100));
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::BudgetExhausted {
+                pc: 0,
+                executed: 100
+            })
+        );
+
+        // plenty of budget is fine
+        let mut machine = Machine::new(&[1101, 1, 1, 0, 99]);
+        machine.set_budget(Some(2));
+        assert_eq!(machine.run(), Ok(vec![]));
+        assert_eq!(machine.executed(), 2);
+    }
+
+    #[test]
+    fn loops() {
+        // jumps between 0 and 3 forever
+        let mut machine = Machine::new(&[1105, 1, 3, 1105, 1, 0]);
+        machine.detect_loops();
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::InfiniteLoop {
+                pc: 3,
+                first_seen: 1,
+                seen_again: 3
+            })
+        );
+
+        // a counter never repeats, so only the budget stops it
+        let mut machine = Machine::new(&[101, 1, 7, 7, 1105, 1, 0, 0]);
+        machine.detect_loops();
+        machine.set_budget(Some(1_000));
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::BudgetExhausted {
+                pc: 0,
+                executed: 1_000
+            })
+        );
+    }
+
+    #[test]
+    fn stepping_back() {
+        // the counter again, which never repeats a state
+        let mut machine = Machine::new(&[101, 1, 7, 7, 1105, 1, 0, 0]);
+        machine.detect_loops();
+        machine.record_history();
+        machine.set_budget(Some(7));
+        for _ in 0..6 {
+            machine.step().unwrap();
+        }
+        machine.step_back();
+        machine.step_back();
+        assert_eq!(machine.executed(), 4);
+
+        // the undone steps are neither a loop nor counted in the budget
+        for _ in 0..3 {
+            assert_eq!(machine.step(), Ok(None));
+        }
+        assert_eq!(machine.executed(), 7);
+        assert_eq!(
+            machine.step(),
+            Err(IntcodeError::BudgetExhausted { pc: 4, executed: 7 })
+        );
+    }
+
+    #[test]
+    fn hash_collisions() {
+        // the counter, which after three instructions is at 4 with 2 at [7]
+        let program = [101, 1, 7, 7, 1105, 1, 0, 0];
+        let mut later = Machine::new(&program);
+        for _ in 0..3 {
+            later.step().unwrap();
+        }
+        let hash = state_hash(later.pc, later.relative_base, &later.memory);
+
+        // pretend that state's hash was seen after one instruction, at 4
+        // but with 1 at [7]
+        let mut machine = Machine::new(&program);
+        machine.detect_loops();
+        machine.step().unwrap();
+        machine.step().unwrap();
+        let seen_states = machine.seen_states.as_mut().unwrap();
+        seen_states.seen.insert(hash, (1, 4, 0));
+
+        assert_eq!(machine.step(), Ok(None));
+        assert_eq!(machine.read(7), 2);
+    }
+
+    #[test]
+    fn io_is_not_a_loop() {
+        // echoes its input forever, every pass ends in the same state but
+        // it reads a value in between
+        let mut machine = Machine::new(&[3, 7, 4, 7, 1105, 1, 0, 0]);
+        machine.detect_loops();
+        machine.provide_inputs(vec![1, 1, 1]);
+
+        assert_eq!(machine.run(), Err(IntcodeError::InputExhausted { pc: 0 }));
+        assert_eq!(machine.outputs(), &[1, 1, 1]);
+    }
+}
@@ -0,0 +1,121 @@
+// Reads programs in the usual puzzle input format, comma separated numbers
+// with any amount of whitespace and newlines around them.
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    // offset is the byte offset of the token in the text
+    BadToken { offset: usize, token: String },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "couldn't read program: {}", err),
+            LoadError::BadToken { offset, token } => {
+                write!(f, "bad value {:?} at offset {}", token, offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> LoadError {
+        LoadError::Io(err)
+    }
+}
+
+pub fn parse_program(text: &str) -> Result<Vec<i64>, LoadError> {
+    if text.trim().is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut program = vec![];
+    let mut offset = 0;
+    for raw in text.split(',') {
+        let token = raw.trim();
+        let token_offset = offset + (raw.len() - raw.trim_start().len());
+        match token.parse() {
+            Ok(value) => program.push(value),
+            Err(_) => {
+                return Err(LoadError::BadToken {
+                    offset: token_offset,
+                    token: token.to_string(),
+                })
+            }
+        }
+
+        // step past the token and its comma
+        offset += raw.len() + 1;
+    }
+
+    Ok(program)
+}
+
+pub fn load_reader<R: Read>(mut reader: R) -> Result<Vec<i64>, LoadError> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+
+    parse_program(&text)
+}
+
+pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Vec<i64>, LoadError> {
+    parse_program(&fs::read_to_string(path)?)
+}
+
+pub fn load_stdin() -> Result<Vec<i64>, LoadError> {
+    load_reader(io::stdin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses() {
+        assert_eq!(parse_program("1,0,0,3,99").unwrap(), vec![1, 0, 0, 3, 99]);
+        assert_eq!(
+            parse_program(" 1101, 100,\n-1,4,\t0\n\n").unwrap(),
+            vec![1101, 100, -1, 4, 0]
+        );
+        assert_eq!(parse_program("\n").unwrap(), vec![]);
+        assert_eq!(
+            load_reader("104,1125899906842624,99\n".as_bytes()).unwrap(),
+            vec![104, 1125899906842624, 99]
+        );
+    }
+
+    #[test]
+    fn bad_tokens() {
+        match parse_program("1,0,x0,3") {
+            Err(LoadError::BadToken { offset, token }) => {
+                assert_eq!((offset, token.as_str()), (4, "x0"));
+            }
+            other => panic!("expected a bad token, got {:?}", other),
+        }
+        match parse_program("1,2,,3") {
+            Err(LoadError::BadToken { offset, token }) => {
+                assert_eq!((offset, token.as_str()), (4, ""));
+            }
+            other => panic!("expected a bad token, got {:?}", other),
+        }
+        assert_eq!(
+            parse_program("3,\n  4 5").unwrap_err().to_string(),
+            "bad value \"4 5\" at offset 5"
+        );
+    }
+
+    #[test]
+    fn missing_file() {
+        match load_file("no/such/program.txt") {
+            Err(LoadError::Io(_)) => {}
+            other => panic!("expected an io error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+// Writes this far past the end of the dense memory go into a map instead of
+// growing the vector all the way out to them.
+pub(super) const SPARSE_GAP: usize = 1 << 16;
+
+// splitmix64's finalizer, spreads the bits of x over the whole word.
+pub fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// A cell's share of the memory hash. Zero cells don't count, so growing
+// memory leaves the hash alone.
+fn cell_hash(address: usize, value: i64) -> u64 {
+    if value == 0 {
+        0
+    } else {
+        mix(address as u64 ^ mix(value as u64))
+    }
+}
+
+// Intcode memory. Every address reads as zero until it is written, and
+// writing past the end grows memory to fit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Memory {
+    dense: Vec<i64>,
+    sparse: HashMap<usize, i64>,
+    // the sum of every cell's hash, kept up to date on each write
+    hash: u64,
+}
+
+impl Memory {
+    pub fn new(program: &[i64]) -> Memory {
+        let hash = program
+            .iter()
+            .enumerate()
+            .fold(0u64, |hash, (address, value)| {
+                hash.wrapping_add(cell_hash(address, *value))
+            });
+
+        Memory {
+            dense: program.to_vec(),
+            sparse: HashMap::new(),
+            hash,
+        }
+    }
+
+    // A hash of everything in memory, cheap to ask for at any time.
+    pub fn content_hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn read(&self, address: usize) -> i64 {
+        match self.dense.get(address) {
+            Some(value) => *value,
+            None => *self.sparse.get(&address).unwrap_or(&0),
+        }
+    }
+
+    pub fn write(&mut self, address: usize, value: i64) {
+        self.hash = self
+            .hash
+            .wrapping_sub(cell_hash(address, self.read(address)))
+            .wrapping_add(cell_hash(address, value));
+
+        if address < self.dense.len() {
+            self.dense[address] = value;
+        } else if address < self.dense.len() + SPARSE_GAP {
+            self.grow(address + 1);
+            self.dense[address] = value;
+        } else {
+            self.sparse.insert(address, value);
+        }
+    }
+
+    fn grow(&mut self, len: usize) {
+        self.dense.resize(len, 0);
+
+        // anything already written in the newly dense range moves over
+        let moved: Vec<usize> = self
+            .sparse
+            .keys()
+            .filter(|address| **address < len)
+            .cloned()
+            .collect();
+        for address in moved {
+            let value = self.sparse.remove(&address).unwrap();
+            self.dense[address] = value;
+        }
+    }
+
+    // Rebuilds memory from as_slice and sparse_entries.
+    pub fn from_parts(dense: Vec<i64>, sparse: &[(usize, i64)]) -> Memory {
+        let mut memory = Memory::new(&dense);
+        for (address, value) in sparse {
+            memory.write(*address, *value);
+        }
+
+        memory
+    }
+
+    // The cells written far past the end of contiguous memory, in address order.
+    pub fn sparse_entries(&self) -> Vec<(usize, i64)> {
+        let mut entries: Vec<(usize, i64)> = self
+            .sparse
+            .iter()
+            .map(|(address, value)| (*address, *value))
+            .collect();
+        entries.sort();
+
+        entries
+    }
+
+    // The contiguous memory starting at address 0. This is the program plus
+    // any cells it has grown into, but not sparse writes far past the end.
+    pub fn as_slice(&self) -> &[i64] {
+        &self.dense
+    }
+
+    pub fn into_vec(self) -> Vec<i64> {
+        self.dense
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grows_on_write() {
+        let mut memory = Memory::new(&[1, 2, 3]);
+
+        assert_eq!(memory.read(1), 2);
+        assert_eq!(memory.read(10), 0);
+
+        memory.write(5, 7);
+        assert_eq!(memory.as_slice(), &[1, 2, 3, 0, 0, 7]);
+        assert_eq!(memory.read(5), 7);
+    }
+
+    #[test]
+    fn sparse_far_writes() {
+        let mut memory = Memory::new(&[1, 2, 3]);
+
+        memory.write(1_000_000_000, 42);
+        assert_eq!(memory.read(1_000_000_000), 42);
+        assert_eq!(memory.as_slice().len(), 3);
+
+        // growing over a sparse address keeps its value
+        let far = 3 + SPARSE_GAP;
+        memory.write(far, 7);
+        assert_eq!(memory.as_slice().len(), 3);
+
+        memory.write(10, 1);
+        memory.write(SPARSE_GAP + 5, 1);
+        assert_eq!(memory.as_slice().len(), SPARSE_GAP + 6);
+        assert_eq!(memory.as_slice()[far], 7);
+    }
+
+    #[test]
+    fn hash_follows_contents() {
+        let mut memory = Memory::new(&[1, 2, 3]);
+        let start = memory.content_hash();
+
+        memory.write(1, 5);
+        memory.write(1_000_000, 9);
+        assert_ne!(memory.content_hash(), start);
+
+        memory.write(1, 2);
+        memory.write(1_000_000, 0);
+        memory.write(20, 0);
+        assert_eq!(memory.content_hash(), start);
+        assert_eq!(Memory::new(&[1, 2, 3, 0, 0]).content_hash(), start);
+    }
+}
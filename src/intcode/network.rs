@@ -0,0 +1,344 @@
+// Many machines running the same program on one network. Each is booted
+// with its address as its first input, then sends packets as three outputs
+// (destination, X, Y) and reads packets as two inputs (X, Y), or -1 when it
+// has nothing waiting.
+//
+// Packets for NAT_ADDRESS go to the NAT, which keeps only the latest one.
+// When the whole network goes idle the NAT sends that packet to address 0.
+//
+// Machines take turns in address order. A turn lasts until the machine has
+// been fed once and asks for input again, or QUANTUM instructions pass, so
+// a run is the same every time no matter how the programs behave.
+use std::collections::VecDeque;
+
+use super::{IntcodeError, Machine, Status};
+
+pub const NAT_ADDRESS: i64 = 255;
+
+const QUANTUM: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Packet {
+    pub dest: i64,
+    pub x: i64,
+    pub y: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    // A machine sent a packet, to another machine or the NAT.
+    Sent { from: usize, packet: Packet },
+    // A packet went nowhere, its destination isn't on the network or has
+    // halted.
+    Dropped { from: usize, packet: Packet },
+    // The network was idle so the NAT woke up address 0.
+    NatSent(Packet),
+}
+
+pub struct Network {
+    machines: Vec<Machine>,
+    queues: Vec<VecDeque<(i64, i64)>>,
+    // output words waiting for the rest of their packet
+    partial: Vec<Vec<i64>>,
+    // machines that found nothing to read and sent nothing on their last turn
+    idle: Vec<bool>,
+    halted: Vec<bool>,
+    nat: Option<Packet>,
+}
+
+impl Network {
+    pub fn new(program: &[i64], size: usize) -> Network {
+        let machines = (0..size)
+            .map(|address| {
+                let mut machine = Machine::new(program);
+                machine.provide_input(address as i64);
+                machine
+            })
+            .collect();
+
+        Network {
+            machines,
+            queues: vec![VecDeque::new(); size],
+            partial: vec![vec![]; size],
+            idle: vec![false; size],
+            halted: vec![false; size],
+            nat: None,
+        }
+    }
+
+    // Halted machines count as idle, whatever is left in their queues.
+    pub fn is_idle(&self) -> bool {
+        (0..self.machines.len())
+            .all(|i| self.halted[i] || (self.idle[i] && self.queues[i].is_empty()))
+    }
+
+    fn send(&mut self, from: usize, packet: Packet, events: &mut Vec<Event>) {
+        if packet.dest == NAT_ADDRESS {
+            self.nat = Some(packet);
+        } else if packet.dest >= 0
+            && (packet.dest as usize) < self.queues.len()
+            && !self.halted[packet.dest as usize]
+        {
+            self.queues[packet.dest as usize].push_back((packet.x, packet.y));
+        } else {
+            events.push(Event::Dropped { from, packet });
+            return;
+        }
+
+        events.push(Event::Sent { from, packet });
+    }
+
+    fn turn(&mut self, address: usize, events: &mut Vec<Event>) -> Result<(), IntcodeError> {
+        let mut fed = false;
+        let mut idle = true;
+
+        for _ in 0..QUANTUM {
+            match self.machines[address].step()? {
+                None => {}
+                Some(Status::Output(value)) => {
+                    idle = false;
+                    self.partial[address].push(value);
+                    if self.partial[address].len() == 3 {
+                        let words: Vec<i64> = self.partial[address].drain(..).collect();
+                        let packet = Packet {
+                            dest: words[0],
+                            x: words[1],
+                            y: words[2],
+                        };
+                        self.send(address, packet, events);
+                    }
+                }
+                Some(Status::NeedsInput) => {
+                    if fed {
+                        break;
+                    }
+                    fed = true;
+
+                    let machine = &mut self.machines[address];
+                    match self.queues[address].pop_front() {
+                        Some((x, y)) => {
+                            idle = false;
+                            machine.provide_inputs(vec![x, y]);
+                        }
+                        None => machine.provide_input(-1),
+                    }
+                }
+                Some(Status::Halted) => {
+                    self.halted[address] = true;
+                    break;
+                }
+            }
+        }
+
+        self.idle[address] = idle && fed;
+        Ok(())
+    }
+
+    // Gives every machine one turn, then lets the NAT act if the network
+    // has gone quiet. Returns everything that happened, in order.
+    pub fn round(&mut self) -> Result<Vec<Event>, IntcodeError> {
+        let mut events = vec![];
+
+        for address in 0..self.machines.len() {
+            if !self.halted[address] {
+                self.turn(address, &mut events)?;
+            }
+        }
+
+        if self.is_idle() {
+            if let Some(packet) = self.nat {
+                self.queues[0].push_back((packet.x, packet.y));
+                events.push(Event::NatSent(Packet { dest: 0, ..packet }));
+            }
+        }
+
+        Ok(events)
+    }
+
+    // Runs rounds until one produces an event the callback wants, every
+    // machine halts, or the network goes idle with nothing for the NAT to
+    // send, after which nothing would ever happen again.
+    pub fn run_until<F>(&mut self, mut want: F) -> Result<Option<Event>, IntcodeError>
+    where
+        F: FnMut(&Event) -> bool,
+    {
+        while !self.halted.iter().all(|halted| *halted) {
+            for event in self.round()? {
+                if want(&event) {
+                    return Ok(Some(event));
+                }
+            }
+
+            if self.is_idle() && self.nat.is_none() {
+                break;
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+// The Y value of the first packet sent to the NAT.
+pub fn first_nat_y(program: &[i64], size: usize) -> Result<Option<i64>, IntcodeError> {
+    let event = Network::new(program, size).run_until(|event| match event {
+        Event::Sent { packet, .. } => packet.dest == NAT_ADDRESS,
+        _ => false,
+    })?;
+
+    Ok(event.map(|event| match event {
+        Event::Sent { packet, .. } => packet.y,
+        _ => unreachable!(),
+    }))
+}
+
+// The first Y value the NAT sends to address 0 twice in a row.
+pub fn repeated_nat_y(program: &[i64], size: usize) -> Result<Option<i64>, IntcodeError> {
+    let mut last = None;
+    let event = Network::new(program, size).run_until(|event| match event {
+        Event::NatSent(packet) => {
+            let repeated = last == Some(packet.y);
+            last = Some(packet.y);
+            repeated
+        }
+        _ => false,
+    })?;
+
+    Ok(event.map(|_| last.unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    // Address 0 boots by sending a packet to 1. Every machine forwards what
+    // it gets to the next address, or to the NAT from the last one, adding
+    // its own address to Y. Address 0 starts Y over from zero.
+    const RELAY: &str = "
+                IN [addr]
+                JT [addr] #listen
+                OUT #1
+                OUT #0
+                OUT #100
+        listen: IN [x]
+                EQ [x] #-1 [tmp]
+                JT [tmp] #listen
+                IN [y]
+                JT [addr] #keep
+                ADD #0 #0 [y]
+        keep:   ADD [y] [addr] [y]
+                ADD [addr] #1 [dest]
+                EQ [dest] [size] [tmp]
+                JF [tmp] #send
+                ADD #255 #0 [dest]
+        send:   OUT [dest]
+                OUT [x]
+                OUT [y]
+                JT #1 #listen
+        addr:   DATA 0
+        x:      DATA 0
+        y:      DATA 0
+        dest:   DATA 0
+        tmp:    DATA 0
+        size:   DATA 4
+    ";
+
+    #[test]
+    fn relay() {
+        let program = assemble(RELAY).unwrap();
+
+        assert_eq!(first_nat_y(&program, 4), Ok(Some(106)));
+        assert_eq!(repeated_nat_y(&program, 4), Ok(Some(6)));
+    }
+
+    #[test]
+    fn quiet() {
+        // reads forever and never sends anything
+        assert_eq!(first_nat_y(&[3, 100, 1105, 1, 0], 2), Ok(None));
+        assert_eq!(repeated_nat_y(&[3, 100, 1105, 1, 0], 2), Ok(None));
+    }
+
+    #[test]
+    fn sending_to_halted() {
+        // one machine halts as it boots, the other sends it a packet then
+        // waits forever
+        let program = |halts: &str, dest: i64| {
+            let source = format!(
+                "
+                        IN [addr]
+                        {} [addr] #halt
+                        OUT #{}
+                        OUT #2
+                        OUT #3
+                wait:   IN [x]
+                        JT #1 #wait
+                halt:   HLT
+                addr:   DATA 0
+                x:      DATA 0
+                ",
+                halts, dest
+            );
+            assemble(&source).unwrap()
+        };
+        let packet = |dest| Packet { dest, x: 2, y: 3 };
+
+        // 1 halts after 0 has already sent to it, leaving it a packet it
+        // will never read
+        let late = program("JT", 1);
+        let mut network = Network::new(&late, 2);
+        assert_eq!(
+            network.round(),
+            Ok(vec![Event::Sent {
+                from: 0,
+                packet: packet(1)
+            }])
+        );
+        assert_eq!(network.round(), Ok(vec![]));
+        assert!(network.is_idle());
+        assert_eq!(first_nat_y(&late, 2), Ok(None));
+
+        // 0 has halted by the time 1 sends to it
+        let early = program("JF", 0);
+        let mut network = Network::new(&early, 2);
+        assert_eq!(
+            network.round(),
+            Ok(vec![Event::Dropped {
+                from: 1,
+                packet: packet(0)
+            }])
+        );
+        assert_eq!(first_nat_y(&early, 2), Ok(None));
+    }
+
+    #[test]
+    fn deterministic() {
+        let program = assemble(RELAY).unwrap();
+        let events = || {
+            let mut network = Network::new(&program, 4);
+            let mut all = vec![];
+            for _ in 0..20 {
+                all.extend(network.round().unwrap());
+            }
+            all
+        };
+
+        let first = events();
+        assert_eq!(
+            first[0],
+            Event::Sent {
+                from: 0,
+                packet: Packet {
+                    dest: 1,
+                    x: 0,
+                    y: 100
+                }
+            }
+        );
+        assert!(first.contains(&Event::NatSent(Packet {
+            dest: 0,
+            x: 0,
+            y: 106
+        })));
+        assert_eq!(first, events());
+    }
+}
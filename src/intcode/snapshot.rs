@@ -0,0 +1,312 @@
+// Saves a machine to text and restores it later, to carry on exactly where
+// it stopped. The format is line based and versioned by its first line:
+//
+//   intcode-snapshot 1
+//   pc 2
+//   relative_base 0
+//   executed 12
+//   budget 1000
+//   input 5,6
+//   output 42
+//   memory 3,0,4,0,99
+//   sparse 100000=7,200000=8
+//
+// Lists are comma separated and may be empty, as is budget when there isn't
+// one. Sparse memory holds the cells written far past the end of contiguous
+// memory, as address=value. The undo log, loop detection and registered
+// extensions aren't saved, a restored machine starts without them.
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+use super::{Machine, Memory, NoTrace, Tracer};
+
+pub const VERSION: u32 = 1;
+
+const MAGIC: &str = "intcode-snapshot";
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    UnsupportedVersion(String),
+    // line is 1 based
+    Malformed { line: usize, reason: String },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "couldn't read snapshot: {}", err),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {}", version)
+            }
+            SnapshotError::Malformed { line, reason } => {
+                write!(f, "bad snapshot line {}: {}", line, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> SnapshotError {
+        SnapshotError::Io(err)
+    }
+}
+
+fn join<I: IntoIterator<Item = String>>(items: I) -> String {
+    items.into_iter().collect::<Vec<String>>().join(",")
+}
+
+fn numbers(values: &[i64]) -> String {
+    join(values.iter().map(|value| value.to_string()))
+}
+
+pub fn save<T: Tracer, W: Write>(machine: &Machine<T>, mut out: W) -> io::Result<()> {
+    let input: Vec<i64> = machine.input.iter().cloned().collect();
+    let sparse = machine
+        .memory
+        .sparse_entries()
+        .into_iter()
+        .map(|(address, value)| format!("{}={}", address, value));
+
+    writeln!(out, "{} {}", MAGIC, VERSION)?;
+    writeln!(out, "pc {}", machine.pc)?;
+    writeln!(out, "relative_base {}", machine.relative_base)?;
+    writeln!(out, "executed {}", machine.executed)?;
+    match machine.budget {
+        Some(budget) => writeln!(out, "budget {}", budget)?,
+        None => writeln!(out, "budget")?,
+    }
+    writeln!(out, "input {}", numbers(&input))?;
+    writeln!(out, "output {}", numbers(&machine.output))?;
+    writeln!(out, "memory {}", numbers(machine.memory.as_slice()))?;
+    writeln!(out, "sparse {}", join(sparse))?;
+
+    out.flush()
+}
+
+pub fn save_file<T: Tracer, P: AsRef<Path>>(machine: &Machine<T>, path: P) -> io::Result<()> {
+    save(machine, io::BufWriter::new(File::create(path)?))
+}
+
+// Reads the lines of a snapshot one field at a time.
+struct Fields<R: BufRead> {
+    lines: io::Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> Fields<R> {
+    fn malformed(&self, reason: String) -> SnapshotError {
+        SnapshotError::Malformed {
+            line: self.line,
+            reason,
+        }
+    }
+
+    fn next(&mut self, name: &str) -> Result<String, SnapshotError> {
+        self.line += 1;
+        let line = match self.lines.next() {
+            Some(line) => line?,
+            None => return Err(self.malformed(format!("missing {}", name))),
+        };
+
+        let mut parts = line.splitn(2, ' ');
+        if parts.next() != Some(name) {
+            return Err(self.malformed(format!("expected {}", name)));
+        }
+
+        Ok(parts.next().unwrap_or("").trim().to_string())
+    }
+
+    fn number<N: std::str::FromStr>(&self, text: &str) -> Result<N, SnapshotError> {
+        text.parse()
+            .map_err(|_| self.malformed(format!("bad number {:?}", text)))
+    }
+
+    fn list(&self, text: &str) -> Result<Vec<i64>, SnapshotError> {
+        if text.is_empty() {
+            return Ok(vec![]);
+        }
+        text.split(',').map(|item| self.number(item)).collect()
+    }
+}
+
+pub fn restore<R: Read>(reader: R) -> Result<Machine, SnapshotError> {
+    let mut fields = Fields {
+        lines: BufReader::new(reader).lines(),
+        line: 0,
+    };
+
+    let version = fields.next(MAGIC)?;
+    if version != VERSION.to_string() {
+        return Err(SnapshotError::UnsupportedVersion(version));
+    }
+
+    let pc = fields.next("pc")?;
+    let pc = fields.number(&pc)?;
+    let relative_base = fields.next("relative_base")?;
+    let relative_base = fields.number(&relative_base)?;
+    let executed = fields.next("executed")?;
+    let executed = fields.number(&executed)?;
+    let budget = fields.next("budget")?;
+    let budget = match budget.as_str() {
+        "" => None,
+        budget => Some(fields.number(budget)?),
+    };
+    let input = fields.next("input")?;
+    let input: VecDeque<i64> = fields.list(&input)?.into_iter().collect();
+    let output = fields.next("output")?;
+    let output = fields.list(&output)?;
+    let memory = fields.next("memory")?;
+    let memory = fields.list(&memory)?;
+
+    let sparse_text = fields.next("sparse")?;
+    let mut sparse = vec![];
+    if !sparse_text.is_empty() {
+        for entry in sparse_text.split(',') {
+            let mut parts = entry.splitn(2, '=');
+            let address = fields.number(parts.next().unwrap())?;
+            let value = match parts.next() {
+                Some(value) => fields.number(value)?,
+                None => return Err(fields.malformed(format!("bad sparse entry {:?}", entry))),
+            };
+            sparse.push((address, value));
+        }
+    }
+
+    Ok(Machine {
+        memory: Memory::from_parts(memory, &sparse),
+        pc,
+        relative_base,
+        input,
+        output,
+        history: None,
+        executed,
+        budget,
+        seen_states: None,
+        extensions: HashMap::new(),
+        decoded: None,
+        tracer: NoTrace,
+    })
+}
+
+pub fn restore_file<P: AsRef<Path>>(path: P) -> Result<Machine, SnapshotError> {
+    restore(File::open(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::fixtures::COMPARE;
+    use crate::intcode::{IntcodeError, Status};
+
+    fn round_trip(machine: &Machine) -> Machine {
+        let mut saved = vec![];
+        save(machine, &mut saved).unwrap();
+
+        restore(saved.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn text_format() {
+        let mut machine = Machine::new(&[3, 0, 4, 0, 99]);
+        machine.provide_inputs(vec![42, 5, 6]);
+        assert_eq!(machine.resume().unwrap(), Status::Output(42));
+        machine.memory.write(1_000_000, 7);
+
+        let mut saved = vec![];
+        save(&machine, &mut saved).unwrap();
+        assert_eq!(
+            String::from_utf8(saved).unwrap(),
+            concat!(
+                "intcode-snapshot 1\n",
+                "pc 4\n",
+                "relative_base 0\n",
+                "executed 2\n",
+                "budget\n",
+                "input 5,6\n",
+                "output 42\n",
+                "memory 42,0,4,0,99\n",
+                "sparse 1000000=7\n",
+            )
+        );
+    }
+
+    #[test]
+    fn resumes_where_it_stopped() {
+        for input in &[7, 8, 9] {
+            let mut straight = Machine::new(&COMPARE);
+            straight.provide_input(*input);
+            let expected = straight.run().unwrap();
+
+            // stop before the input is read, and again halfway through
+            let mut machine = Machine::new(&COMPARE);
+            assert_eq!(machine.resume().unwrap(), Status::NeedsInput);
+            let mut machine = round_trip(&machine);
+            machine.provide_input(*input);
+            for _ in 0..4 {
+                machine.step().unwrap();
+            }
+
+            let mut machine = round_trip(&machine);
+            assert_eq!(machine.run().unwrap(), expected);
+            assert_eq!(machine.outputs(), &expected[..]);
+            assert_eq!(machine.memory(), straight.memory());
+        }
+    }
+
+    #[test]
+    fn sparse_and_relative() {
+        let mut machine = Machine::new(&[109, 5, 21101, 3, 4, 1_000_000, 204, 1_000_000, 99]);
+        machine.step().unwrap();
+        machine.step().unwrap();
+
+        let mut machine = round_trip(&machine);
+        assert_eq!(machine.relative_base(), 5);
+        assert_eq!(machine.read(1_000_005), 7);
+        assert_eq!(machine.run().unwrap(), vec![7]);
+    }
+
+    #[test]
+    fn budget_carries_on() {
+        // loops forever
+        let mut machine = Machine::new(&[1105, 1, 0]);
+        machine.set_budget(Some(10));
+        for _ in 0..4 {
+            machine.step().unwrap();
+        }
+
+        let mut machine = round_trip(&machine);
+        assert_eq!(machine.executed(), 4);
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::BudgetExhausted {
+                pc: 0,
+                executed: 10
+            })
+        );
+    }
+
+    #[test]
+    fn bad_snapshots() {
+        match restore("intcode-snapshot 2\n".as_bytes()) {
+            Err(SnapshotError::UnsupportedVersion(version)) => assert_eq!(version, "2"),
+            other => panic!("expected a version error, got {:?}", other.err()),
+        }
+        match restore("intcode-snapshot 1\npc 4\nrelative_base x\n".as_bytes()) {
+            Err(err) => assert_eq!(err.to_string(), "bad snapshot line 3: bad number \"x\""),
+            Ok(_) => panic!("expected a bad number"),
+        }
+        match restore("intcode-snapshot 1\npc 4\n".as_bytes()) {
+            Err(err) => assert_eq!(
+                err.to_string(),
+                "bad snapshot line 3: missing relative_base"
+            ),
+            Ok(_) => panic!("expected a missing field"),
+        }
+    }
+}
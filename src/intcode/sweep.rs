@@ -0,0 +1,250 @@
+// Runs many variations of one program across a pool of threads, looking for
+// the ones whose result passes some test. Each candidate patches some memory
+// cells before the program starts and/or gives it some input.
+//
+// Results always come back in candidate order, however the threads happen
+// to get through them, and first gives the earliest matching candidate in
+// that order rather than whichever match was found first.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use super::{DecodeCache, IntcodeError, Machine};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Candidate {
+    // (address, value) pairs written into the program before it runs
+    pub patches: Vec<(usize, i64)>,
+    pub inputs: Vec<i64>,
+}
+
+impl Candidate {
+    pub fn patches(patches: Vec<(usize, i64)>) -> Candidate {
+        Candidate {
+            patches,
+            inputs: vec![],
+        }
+    }
+
+    pub fn inputs(inputs: Vec<i64>) -> Candidate {
+        Candidate {
+            patches: vec![],
+            inputs,
+        }
+    }
+}
+
+// A finished run of a candidate.
+#[derive(Debug, PartialEq)]
+pub struct Run {
+    pub memory: Vec<i64>,
+    pub output: Vec<i64>,
+}
+
+// A candidate that matched, by its index in the list swept over.
+#[derive(Debug, PartialEq)]
+pub struct Found {
+    pub index: usize,
+    pub result: Result<Run, IntcodeError>,
+}
+
+pub struct Sweep<'a> {
+    program: &'a [i64],
+    threads: usize,
+    budget: Option<u64>,
+}
+
+impl<'a> Sweep<'a> {
+    // A sweep over program using a thread per core.
+    pub fn new(program: &'a [i64]) -> Sweep<'a> {
+        Sweep {
+            program,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            budget: None,
+        }
+    }
+
+    pub fn threads(mut self, threads: usize) -> Sweep<'a> {
+        self.threads = threads.max(1);
+        self
+    }
+
+    // An instruction budget for each run, so one that never halts doesn't
+    // hold up the sweep.
+    pub fn budget(mut self, budget: u64) -> Sweep<'a> {
+        self.budget = Some(budget);
+        self
+    }
+
+    // The earliest candidate whose result matches.
+    pub fn first<P>(&self, candidates: &[Candidate], matches: P) -> Option<Found>
+    where
+        P: Fn(&Result<Run, IntcodeError>) -> bool + Sync,
+    {
+        self.sweep(candidates, &matches, true).pop()
+    }
+
+    // Every candidate whose result matches, in order.
+    pub fn all<P>(&self, candidates: &[Candidate], matches: P) -> Vec<Found>
+    where
+        P: Fn(&Result<Run, IntcodeError>) -> bool + Sync,
+    {
+        self.sweep(candidates, &matches, false)
+    }
+
+    fn run(&self, candidate: &Candidate, cache: &DecodeCache) -> Result<Run, IntcodeError> {
+        let mut machine = Machine::from_cache(cache);
+        // written like any other store, so a far address is kept sparsely
+        // and the cache knows what was patched
+        for &(address, value) in &candidate.patches {
+            machine.write(address, value);
+        }
+        machine.set_budget(self.budget);
+        machine.provide_inputs(candidate.inputs.iter().cloned());
+        let output = machine.run()?;
+
+        Ok(Run {
+            memory: machine.into_memory(),
+            output,
+        })
+    }
+
+    fn sweep<P>(&self, candidates: &[Candidate], matches: &P, stop_at_first: bool) -> Vec<Found>
+    where
+        P: Fn(&Result<Run, IntcodeError>) -> bool + Sync,
+    {
+        // candidates are handed out in order, and once one has matched
+        // there's no need to try any after it
+        let next = AtomicUsize::new(0);
+        let earliest = AtomicUsize::new(usize::MAX);
+        let found = Mutex::new(vec![]);
+        let cache = DecodeCache::new(self.program);
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= candidates.len() || index > earliest.load(Ordering::Relaxed) {
+                        break;
+                    }
+
+                    let result = self.run(&candidates[index], &cache);
+                    if matches(&result) {
+                        if stop_at_first {
+                            earliest.fetch_min(index, Ordering::Relaxed);
+                        }
+                        found.lock().unwrap().push(Found { index, result });
+                    }
+                });
+            }
+        });
+
+        let mut found = found.into_inner().unwrap();
+        found.sort_by_key(|found| found.index);
+        if stop_at_first {
+            found.truncate(1);
+        }
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+    use crate::intcode::fixtures::COMPARE;
+
+    #[test]
+    fn inputs() {
+        let candidates: Vec<Candidate> = (0..20).map(|n| Candidate::inputs(vec![n])).collect();
+
+        for threads in 1..=4 {
+            let sweep = Sweep::new(&COMPARE).threads(threads);
+            let above: Vec<usize> = sweep
+                .all(&candidates, |result| {
+                    result.as_ref().unwrap().output == vec![1001]
+                })
+                .into_iter()
+                .map(|found| found.index)
+                .collect();
+            assert_eq!(above, (9..20).collect::<Vec<usize>>());
+
+            let found = sweep
+                .first(&candidates, |result| {
+                    result.as_ref().unwrap().output != vec![999]
+                })
+                .unwrap();
+            assert_eq!(found.index, 8);
+        }
+    }
+
+    #[test]
+    fn first_in_order() {
+        // counts down to zero, then outputs 1
+        let program = assemble(
+            "
+            loop:  ADD [count] #-1 [count]
+                   JT [count] #loop
+                   OUT #1
+                   HLT
+            count: DATA 0
+            ",
+        )
+        .unwrap();
+        assert_eq!(program.len(), 11);
+
+        // the first candidate takes far longer than the rest to match
+        let mut candidates = vec![Candidate::patches(vec![(10, 100_000)])];
+        candidates.extend((1..50).map(|n| Candidate::patches(vec![(10, n)])));
+
+        let found = Sweep::new(&program)
+            .threads(4)
+            .first(&candidates, |result| result.is_ok())
+            .unwrap();
+        assert_eq!(found.index, 0);
+        assert_eq!(found.result.unwrap().output, vec![1]);
+    }
+
+    #[test]
+    fn far_patches() {
+        // outputs the word at a far address
+        let program = vec![4, i64::MAX, 99];
+        let candidates = vec![
+            Candidate::patches(vec![(i64::MAX as usize, 7)]),
+            Candidate::patches(vec![(usize::MAX, 1)]),
+        ];
+
+        let found = Sweep::new(&program).all(&candidates, |_| true);
+        assert_eq!(found[0].result.as_ref().unwrap().output, vec![7]);
+        assert_eq!(found[1].result.as_ref().unwrap().output, vec![0]);
+    }
+
+    #[test]
+    fn failures_are_results() {
+        // never halts unless patched
+        let program = vec![1105, 1, 0];
+        let candidates = vec![Candidate::default(), Candidate::patches(vec![(0, 99)])];
+
+        let found = Sweep::new(&program).budget(1000).all(&candidates, |_| true);
+        assert_eq!(
+            found,
+            vec![
+                Found {
+                    index: 0,
+                    result: Err(IntcodeError::BudgetExhausted {
+                        pc: 0,
+                        executed: 1000
+                    })
+                },
+                Found {
+                    index: 1,
+                    result: Ok(Run {
+                        memory: vec![99, 1, 0],
+                        output: vec![]
+                    })
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,448 @@
+// Symbolic execution of day 2 style programs, those made only of Add, Mult
+// and Halt. Some memory cells are taken to be unknowns, and every cell holds
+// a linear expression in them instead of a number. Running the program once
+// gives each cell's final value as an expression, which can then be solved
+// for the unknowns directly instead of trying every combination of them.
+//
+// A cell whose value can't be given that way, because it multiplies two
+// unknowns or was read from an address that depends on them, holds the
+// reason instead. That is only a problem if the cell is later needed, and
+// when it is the caller should fall back to running the program.
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use super::memory::SPARSE_GAP;
+
+// constant + coefficients[0] * x0 + coefficients[1] * x1 + ...
+#[derive(Clone, Debug, PartialEq)]
+pub struct Linear {
+    pub constant: i64,
+    pub coefficients: Vec<i64>,
+}
+
+impl Linear {
+    fn constant(value: i64, unknowns: usize) -> Linear {
+        Linear {
+            constant: value,
+            coefficients: vec![0; unknowns],
+        }
+    }
+
+    fn unknown(index: usize, unknowns: usize) -> Linear {
+        let mut coefficients = vec![0; unknowns];
+        coefficients[index] = 1;
+
+        Linear {
+            constant: 0,
+            coefficients,
+        }
+    }
+
+    // The value, if it doesn't depend on any unknowns.
+    pub fn as_constant(&self) -> Option<i64> {
+        if self.coefficients.iter().all(|c| *c == 0) {
+            Some(self.constant)
+        } else {
+            None
+        }
+    }
+
+    fn add(&self, other: &Linear) -> Option<Linear> {
+        let coefficients = self
+            .coefficients
+            .iter()
+            .zip(&other.coefficients)
+            .map(|(a, b)| a.checked_add(*b))
+            .collect::<Option<Vec<i64>>>()?;
+
+        Some(Linear {
+            constant: self.constant.checked_add(other.constant)?,
+            coefficients,
+        })
+    }
+
+    fn scale(&self, factor: i64) -> Option<Linear> {
+        let coefficients = self
+            .coefficients
+            .iter()
+            .map(|c| c.checked_mul(factor))
+            .collect::<Option<Vec<i64>>>()?;
+
+        Some(Linear {
+            constant: self.constant.checked_mul(factor)?,
+            coefficients,
+        })
+    }
+
+    // The product, unless neither side is a constant.
+    fn mul(&self, other: &Linear) -> Result<Option<Linear>, ()> {
+        match (self.as_constant(), other.as_constant()) {
+            (Some(factor), _) => Ok(other.scale(factor)),
+            (_, Some(factor)) => Ok(self.scale(factor)),
+            _ => Err(()),
+        }
+    }
+
+    pub fn eval(&self, values: &[i64]) -> Option<i64> {
+        self.coefficients
+            .iter()
+            .zip(values)
+            .try_fold(self.constant, |sum, (c, x)| {
+                sum.checked_add(c.checked_mul(*x)?)
+            })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SymbolicError {
+    // an opcode other than Add, Mult or Halt, or one that depends on the unknowns
+    Unsupported { pc: usize },
+    // an address that depends on the unknowns
+    SymbolicAddress { pc: usize },
+    // two expressions in the unknowns multiplied together
+    NonLinear { pc: usize },
+    Overflow { pc: usize },
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolicError::Unsupported { pc } => {
+                write!(f, "can't run the instruction at {} symbolically", pc)
+            }
+            SymbolicError::SymbolicAddress { pc } => {
+                write!(
+                    f,
+                    "instruction at {} uses an address that depends on the unknowns",
+                    pc
+                )
+            }
+            SymbolicError::NonLinear { pc } => {
+                write!(f, "instruction at {} multiplies two unknowns", pc)
+            }
+            SymbolicError::Overflow { pc } => write!(f, "instruction at {} overflowed", pc),
+        }
+    }
+}
+
+impl std::error::Error for SymbolicError {}
+
+// What a cell holds: an expression in the unknowns, or why it can't be
+// given as one.
+pub type Value = Result<Linear, SymbolicError>;
+
+// Memory laid out like the machine's, so the two agree on what a program
+// leaves behind: writes just past the end grow it, and those far past the
+// end are kept to one side.
+struct Cells {
+    dense: Vec<Value>,
+    sparse: HashMap<usize, Value>,
+    zero: Value,
+}
+
+impl Cells {
+    fn get(&self, address: usize) -> &Value {
+        match self.dense.get(address) {
+            Some(value) => value,
+            None => self.sparse.get(&address).unwrap_or(&self.zero),
+        }
+    }
+
+    fn set(&mut self, address: usize, value: Value) {
+        if address < self.dense.len() {
+            self.dense[address] = value;
+        } else if address < self.dense.len() + SPARSE_GAP {
+            let start = self.dense.len();
+            self.dense.resize(address + 1, self.zero.clone());
+            for moved in start..address {
+                if let Some(value) = self.sparse.remove(&moved) {
+                    self.dense[moved] = value;
+                }
+            }
+            self.dense[address] = value;
+        } else {
+            self.sparse.insert(address, value);
+        }
+    }
+}
+
+// Runs program with the cells at unknowns left as unknowns, in that order,
+// and returns the final memory up to where it stops being contiguous, as
+// Machine::into_memory does. Cells can end up with values that aren't linear
+// without it being an error, so long as nothing needs them to be; using one
+// as an opcode or an address to write to is, as is overflow that doesn't
+// depend on the unknowns.
+pub fn run(program: &[i64], unknowns: &[usize]) -> Result<Vec<Value>, SymbolicError> {
+    let mut memory = Cells {
+        dense: program
+            .iter()
+            .map(|value| Ok(Linear::constant(*value, unknowns.len())))
+            .collect(),
+        sparse: HashMap::new(),
+        zero: Ok(Linear::constant(0, unknowns.len())),
+    };
+    for (i, address) in unknowns.iter().enumerate() {
+        memory.set(*address, Ok(Linear::unknown(i, unknowns.len())));
+    }
+
+    let mut pc = 0;
+    loop {
+        let cell = |address: usize| memory.get(address);
+
+        // the address a parameter names, None if it depends on the unknowns
+        let address = |param: usize| -> Result<Option<usize>, SymbolicError> {
+            let value = match cell(param) {
+                Ok(value) => value,
+                Err(_) => return Ok(None),
+            };
+            match value.as_constant() {
+                Some(address) if address >= 0 => Ok(Some(address as usize)),
+                Some(_) => Err(SymbolicError::Unsupported { pc }),
+                None => Ok(None),
+            }
+        };
+        let read = |param: usize| -> Result<Value, SymbolicError> {
+            Ok(match address(param)? {
+                Some(address) => cell(address).clone(),
+                None => Err(SymbolicError::SymbolicAddress { pc }),
+            })
+        };
+
+        let opcode = match cell(pc) {
+            Ok(value) => value.as_constant(),
+            Err(_) => None,
+        };
+        // overflow between plain numbers fails the run, as it fails the
+        // machine, but in an expression it only spoils the cell
+        let overflowed = |a: &Linear, b: &Linear| match (a.as_constant(), b.as_constant()) {
+            (Some(_), Some(_)) => Err(SymbolicError::Overflow { pc }),
+            _ => Ok(Err(SymbolicError::Overflow { pc })),
+        };
+        let value = match opcode {
+            Some(1) => match (read(pc + 1)?, read(pc + 2)?) {
+                (Ok(a), Ok(b)) => match a.add(&b) {
+                    Some(sum) => Ok(sum),
+                    None => overflowed(&a, &b)?,
+                },
+                (Err(err), _) | (_, Err(err)) => Err(err),
+            },
+            Some(2) => match (read(pc + 1)?, read(pc + 2)?) {
+                (Ok(a), Ok(b)) => match a.mul(&b) {
+                    Ok(Some(product)) => Ok(product),
+                    Ok(None) => overflowed(&a, &b)?,
+                    Err(()) => Err(SymbolicError::NonLinear { pc }),
+                },
+                (Err(err), _) | (_, Err(err)) => Err(err),
+            },
+            Some(99) => return Ok(memory.dense),
+            _ => return Err(SymbolicError::Unsupported { pc }),
+        };
+
+        let dest = address(pc + 3)?.ok_or(SymbolicError::SymbolicAddress { pc })?;
+        memory.set(dest, value);
+        pc += 4;
+    }
+}
+
+// The first values for the unknowns, each taken from its range, that make
+// expr equal goal. Values are tried in order with the first unknown
+// changing slowest, the order a nested loop over the ranges would go in.
+// There must be a range for each unknown in expr.
+pub fn solve(expr: &Linear, goal: i64, ranges: &[RangeInclusive<i64>]) -> Option<Vec<i64>> {
+    if ranges.len() != expr.coefficients.len() {
+        return None;
+    }
+    if ranges.is_empty() {
+        return if expr.constant == goal {
+            Some(vec![])
+        } else {
+            None
+        };
+    }
+
+    let mut values = vec![];
+    solve_from(expr, goal, ranges, &mut values)
+}
+
+fn solve_from(
+    expr: &Linear,
+    goal: i64,
+    ranges: &[RangeInclusive<i64>],
+    values: &mut Vec<i64>,
+) -> Option<Vec<i64>> {
+    let i = values.len();
+    let last = i + 1 == ranges.len();
+    let coefficient = expr.coefficients[i];
+
+    // the last unknown can be solved for, unless nothing depends on it
+    if last && coefficient != 0 {
+        values.push(0);
+        let rest = expr.eval(values)?;
+        values.pop();
+
+        let wanted = goal.checked_sub(rest)?;
+        let value = wanted.checked_div(coefficient)?;
+        if wanted.checked_rem(coefficient)? != 0 || !ranges[i].contains(&value) {
+            return None;
+        }
+        let mut solution = values.clone();
+        solution.push(value);
+        return Some(solution);
+    }
+
+    for value in ranges[i].clone() {
+        values.push(value);
+        let found = if last {
+            if expr.eval(values)? == goal {
+                Some(values.clone())
+            } else {
+                None
+            }
+        } else {
+            solve_from(expr, goal, ranges, values)
+        };
+        values.pop();
+
+        if found.is_some() {
+            return found;
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{IntcodeError, Machine};
+
+    #[test]
+    fn linear_program() {
+        // [0] = ([13] + [14]) * 3 + [13]
+        let program = vec![1, 13, 14, 0, 2, 0, 15, 0, 1, 0, 13, 0, 99, 0, 0, 3];
+        let memory = run(&program, &[13, 14]).unwrap();
+
+        let expr = memory[0].clone().unwrap();
+        assert_eq!(
+            expr,
+            Linear {
+                constant: 0,
+                coefficients: vec![4, 3]
+            }
+        );
+        assert_eq!(solve(&expr, 29, &[0..=9, 0..=9]), Some(vec![2, 7]));
+        assert_eq!(solve(&expr, 2, &[0..=9, 0..=9]), None);
+    }
+
+    #[test]
+    fn unknown_unused() {
+        // [0] = [5] * 5, which doesn't depend on [7] at all
+        let program = vec![2, 5, 6, 0, 99, 0, 5, 0];
+        let expr = run(&program, &[5, 7]).unwrap()[0].clone().unwrap();
+
+        assert_eq!(solve(&expr, 30, &[0..=9, 0..=9]), Some(vec![6, 0]));
+    }
+
+    #[test]
+    fn past_the_end() {
+        // reads zero from [7], and writes to [9]
+        let memory = run(&[1, 7, 0, 9, 99], &[]).unwrap();
+        assert_eq!(memory.len(), 10);
+        assert_eq!(memory[9].as_ref().unwrap().as_constant(), Some(1));
+    }
+
+    #[test]
+    fn far_past_the_end() {
+        // kept to one side, like the machine does, instead of filling in
+        // a trillion cells
+        let memory = run(&[1, 0, 0, 1_000_000_000_000, 99], &[]).unwrap();
+        assert_eq!(memory.len(), 5);
+    }
+
+    #[test]
+    fn awkward_solves() {
+        // nothing to solve for
+        let constant = Linear::constant(7, 0);
+        assert_eq!(solve(&constant, 7, &[]), Some(vec![]));
+        assert_eq!(solve(&constant, 8, &[]), None);
+
+        // a range for an unknown that isn't there, or one missing
+        let expr = Linear::unknown(0, 1);
+        assert_eq!(solve(&expr, 1, &[0..=9, 0..=9]), None);
+        assert_eq!(solve(&Linear::unknown(0, 2), 1, &[0..=9]), None);
+
+        // -x = i64::MIN has no answer that fits
+        let negated = expr.scale(-1).unwrap();
+        assert_eq!(solve(&negated, i64::MIN, &[i64::MIN..=i64::MAX]), None);
+        assert_eq!(solve(&negated, -5, &[0..=9]), Some(vec![5]));
+    }
+
+    #[test]
+    fn overflow() {
+        // squares [17] = 1000 until it overflows on the third go
+        let program = vec![
+            2, 17, 17, 17, 2, 17, 17, 17, 2, 17, 17, 17, 2, 17, 17, 17, 99, 1000,
+        ];
+        assert_eq!(
+            Machine::new(&program).run(),
+            Err(IntcodeError::Overflow { pc: 8 })
+        );
+        assert_eq!(run(&program, &[]), Err(SymbolicError::Overflow { pc: 8 }));
+
+        // [17] unknown times i64::MAX, twice, overflows the expression and
+        // only spoils the cell
+        let program = vec![
+            2,
+            17,
+            16,
+            17,
+            2,
+            17,
+            16,
+            17,
+            99,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            i64::MAX,
+            0,
+        ];
+        let memory = run(&program, &[17]).unwrap();
+        assert_eq!(memory[17], Err(SymbolicError::Overflow { pc: 4 }));
+    }
+
+    #[test]
+    fn falls_over() {
+        assert_eq!(
+            run(&[2, 5, 6, 0, 99, 0, 0], &[5, 6]).unwrap()[0],
+            Err(SymbolicError::NonLinear { pc: 0 })
+        );
+        // reading through an unknown address is fine until the value is used
+        assert_eq!(
+            run(&[1, 1, 2, 0, 99], &[1, 2]).unwrap()[0],
+            Err(SymbolicError::SymbolicAddress { pc: 0 })
+        );
+        assert_eq!(
+            run(&[1, 1, 2, 9, 1, 0, 0, 9, 99, 0], &[1, 2]).unwrap()[9],
+            Ok(Linear {
+                constant: 2,
+                coefficients: vec![0, 0]
+            })
+        );
+
+        // [4] = [9] + [10], so the next instruction's opcode is unknown
+        assert_eq!(
+            run(&[1, 9, 10, 4, 0, 0, 0, 0, 99, 0, 0], &[9, 10]),
+            Err(SymbolicError::Unsupported { pc: 4 })
+        );
+        assert_eq!(
+            run(&[1, 0, 0, 3, 99], &[3]),
+            Err(SymbolicError::SymbolicAddress { pc: 0 })
+        );
+    }
+}
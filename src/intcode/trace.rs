@@ -0,0 +1,319 @@
+use std::io::{self, Write};
+
+use super::disasm::format_param;
+use super::{ArgMode, OpCode};
+
+// One executed instruction, as reported to a Tracer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub pc: usize,
+    pub opcode: OpCode,
+    pub modes: [ArgMode; 3],
+    // the raw parameter words, only the first opcode.arity() are used
+    pub params: [i64; 3],
+    // the value of each parameter that is read, and the address of the
+    // parameter that is written
+    pub args: [i64; 3],
+    // the memory write the instruction made, as (address, value)
+    pub write: Option<(usize, i64)>,
+}
+
+impl Step {
+    pub fn params(&self) -> &[i64] {
+        &self.params[..self.opcode.arity()]
+    }
+
+    pub fn args(&self) -> &[i64] {
+        &self.args[..self.opcode.arity()]
+    }
+}
+
+// An executed instruction with a host defined opcode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExtensionStep {
+    pub pc: usize,
+    pub code: i64,
+    pub name: String,
+    pub modes: Vec<ArgMode>,
+    pub params: Vec<i64>,
+    pub args: Vec<i64>,
+    pub write_param: Option<usize>,
+    pub write: Option<(usize, i64)>,
+}
+
+// Called by the machine after every instruction it executes.
+pub trait Tracer {
+    fn trace(&mut self, step: &Step);
+
+    // Instructions registered as extensions come here instead.
+    fn trace_extension(&mut self, _step: &ExtensionStep) {}
+}
+
+// The default tracer, ignores everything.
+pub struct NoTrace;
+
+impl Tracer for NoTrace {
+    fn trace(&mut self, _step: &Step) {}
+}
+
+// Keeps every step, mostly useful in tests.
+impl Tracer for Vec<Step> {
+    fn trace(&mut self, step: &Step) {
+        self.push(step.clone());
+    }
+}
+
+impl Tracer for Box<dyn Tracer> {
+    fn trace(&mut self, step: &Step) {
+        (**self).trace(step)
+    }
+
+    fn trace_extension(&mut self, step: &ExtensionStep) {
+        (**self).trace_extension(step)
+    }
+}
+
+// Tracers can't return errors mid-run, so writers hold on to the first one
+// and hand it back from finish.
+struct TraceOut<W: Write> {
+    out: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> TraceOut<W> {
+    fn write_line(&mut self, line: &str) {
+        if self.error.is_none() {
+            if let Err(err) = writeln!(self.out, "{}", line) {
+                self.error = Some(err);
+            }
+        }
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        match self.error {
+            Some(err) => Err(err),
+            None => self.out.flush().map(|_| self.out),
+        }
+    }
+}
+
+// Prints each instruction like the disassembler does, followed by the
+// values it read and what it wrote:
+//
+//     6: ADD #1 #238 [225]          1 238 -> [225] = 239
+pub struct TextTracer<W: Write> {
+    out: TraceOut<W>,
+}
+
+impl<W: Write> TextTracer<W> {
+    pub fn new(out: W) -> TextTracer<W> {
+        TextTracer {
+            out: TraceOut { out, error: None },
+        }
+    }
+
+    pub fn finish(self) -> io::Result<W> {
+        self.out.finish()
+    }
+}
+
+fn text_line(
+    pc: usize,
+    name: &str,
+    modes: &[ArgMode],
+    params: &[i64],
+    args: &[i64],
+    write_param: Option<usize>,
+    write: Option<(usize, i64)>,
+) -> String {
+    let mut text = name.to_string();
+    let mut values = vec![];
+    for (i, param) in params.iter().enumerate() {
+        text.push(' ');
+        text.push_str(&format_param(modes[i], *param));
+        if write_param != Some(i) {
+            values.push(args[i].to_string());
+        }
+    }
+    if let Some((address, value)) = write {
+        values.push(format!("-> [{}] = {}", address, value));
+    }
+
+    let line = format!("{:>5}: {:<24}{}", pc, text, values.join(" "));
+    line.trim_end().to_string()
+}
+
+impl<W: Write> Tracer for TextTracer<W> {
+    fn trace(&mut self, step: &Step) {
+        let line = text_line(
+            step.pc,
+            step.opcode.mnemonic(),
+            &step.modes,
+            step.params(),
+            step.args(),
+            step.opcode.write_param(),
+            step.write,
+        );
+        self.out.write_line(&line);
+    }
+
+    fn trace_extension(&mut self, step: &ExtensionStep) {
+        let line = text_line(
+            step.pc,
+            &step.name,
+            &step.modes,
+            &step.params,
+            &step.args,
+            step.write_param,
+            step.write,
+        );
+        self.out.write_line(&line);
+    }
+}
+
+// A compact trace, one line per instruction of space separated numbers:
+//
+//   pc instruction param... ; arg... ; address value
+//
+// where the last section is empty for instructions that don't write.
+pub struct TraceWriter<W: Write> {
+    out: TraceOut<W>,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(out: W) -> TraceWriter<W> {
+        TraceWriter {
+            out: TraceOut { out, error: None },
+        }
+    }
+
+    pub fn finish(self) -> io::Result<W> {
+        self.out.finish()
+    }
+}
+
+fn join(values: &[i64]) -> String {
+    let strings: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+    strings.join(" ")
+}
+
+fn compact_line(
+    pc: usize,
+    code: i64,
+    modes: &[ArgMode],
+    params: &[i64],
+    args: &[i64],
+    write: Option<(usize, i64)>,
+) -> String {
+    let mut instruction = code;
+    let mut scale = 100;
+    for mode in modes {
+        instruction += mode.code() * scale;
+        scale *= 10;
+    }
+    let write = match write {
+        Some((address, value)) => format!("{} {}", address, value),
+        None => String::new(),
+    };
+
+    let mut line = format!("{} {}", pc, instruction);
+    for section in &[
+        join(params),
+        String::from(";"),
+        join(args),
+        String::from(";"),
+        write,
+    ] {
+        if !section.is_empty() {
+            line.push(' ');
+            line.push_str(section);
+        }
+    }
+
+    line
+}
+
+impl<W: Write> Tracer for TraceWriter<W> {
+    fn trace(&mut self, step: &Step) {
+        let line = compact_line(
+            step.pc,
+            step.opcode.code(),
+            &step.modes,
+            step.params(),
+            step.args(),
+            step.write,
+        );
+        self.out.write_line(&line);
+    }
+
+    fn trace_extension(&mut self, step: &ExtensionStep) {
+        let line = compact_line(
+            step.pc,
+            step.code,
+            &step.modes,
+            &step.params,
+            &step.args,
+            step.write,
+        );
+        self.out.write_line(&line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Machine;
+    use super::*;
+
+    #[test]
+    fn collects_steps() {
+        let mut machine = Machine::new(&[1101, 100, -1, 4, 0]).with_tracer(vec![]);
+        machine.run().unwrap();
+
+        assert_eq!(
+            machine.tracer()[0],
+            Step {
+                pc: 0,
+                opcode: OpCode::Add,
+                modes: [ArgMode::Immediate, ArgMode::Immediate, ArgMode::Position],
+                params: [100, -1, 4],
+                args: [100, -1, 4],
+                write: Some((4, 99)),
+            }
+        );
+        assert_eq!(machine.tracer()[1].opcode, OpCode::Halt);
+        assert_eq!(machine.tracer().len(), 2);
+    }
+
+    #[test]
+    fn text_and_compact() {
+        let program = [3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+
+        let mut machine = Machine::new(&program).with_tracer(TextTracer::new(vec![]));
+        machine.provide_input(8);
+        machine.run().unwrap();
+        let text = machine.into_tracer().finish().unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            concat!(
+                "    0: IN [9]                  -> [9] = 8\n",
+                "    2: EQ [9] [10] [9]         8 8 -> [9] = 1\n",
+                "    6: OUT [9]                 1\n",
+                "    8: HLT\n",
+            )
+        );
+
+        let mut machine = Machine::new(&program).with_tracer(TraceWriter::new(vec![]));
+        machine.provide_input(8);
+        machine.run().unwrap();
+        let compact = machine.into_tracer().finish().unwrap();
+        assert_eq!(
+            String::from_utf8(compact).unwrap(),
+            concat!(
+                "0 3 9 ; 9 ; 9 8\n",
+                "2 8 9 10 9 ; 8 8 9 ; 9 1\n",
+                "6 4 9 ; 1 ;\n",
+                "8 99 ; ;\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,6 @@
+pub mod five;
+pub mod four;
+pub mod intcode;
+pub mod one;
+pub mod three;
+pub mod two;
@@ -1,23 +1,19 @@
-mod five;
-mod four;
-mod one;
-mod three;
-mod two;
+use advent::five;
 
 fn main() {
     println!("Hello, world!");
 
-    // println!("one-a: {}", one::one_a());
-    // println!("one-b: {}", one::one_b());
+    // println!("one-a: {}", advent::one::one_a());
+    // println!("one-b: {}", advent::one::one_b());
 
-    // println!("two-a: {}", two::two_a());
-    // println!("two-b: {}", two::two_b());
+    // println!("two-a: {}", advent::two::two_a());
+    // println!("two-b: {}", advent::two::two_b());
 
-    // println!("three-a: {}", three::three_a());
-    // println!("three-b: {}", three::three_b());
+    // println!("three-a: {}", advent::three::three_a());
+    // println!("three-b: {}", advent::three::three_b());
 
-    // println!("four-a: {}", four::four_a());
-    // println!("four-b: {}", four::four_b());
+    // println!("four-a: {}", advent::four::four_a());
+    // println!("four-b: {}", advent::four::four_b());
 
     println!("five-a: {}", five::five_a());
     println!("five-b: {}", five::five_b());